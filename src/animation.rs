@@ -0,0 +1,187 @@
+//! Unit animation helpers.
+//!
+//! Links each unit to the `AnimationPlayer` spawned inside its glTF scene and
+//! plays occasional one-shot idle fidgets that return to the base idle loop.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::{Animations, Gameplay, Movable, Moving};
+
+pub struct UnitAnimationPlugin;
+
+impl Plugin for UnitAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FidgetSettings>()
+            .add_system(link_animation_players.in_set(Gameplay))
+            .add_system(play_movement_animations.in_set(Gameplay))
+            .add_system(track_idle_time.in_set(Gameplay))
+            .add_system(play_idle_fidgets.after(track_idle_time).in_set(Gameplay))
+            .add_system(finish_one_shot_animations.in_set(Gameplay));
+    }
+}
+
+/// Points from a unit's root entity to the `AnimationPlayer` inside its scene.
+#[derive(Component)]
+pub struct AnimationPlayerLink(pub Entity);
+
+/// Clips that can be played once while a unit is standing around.
+#[derive(Resource)]
+pub struct FidgetAnimations(pub Vec<Handle<AnimationClip>>);
+
+#[derive(Resource)]
+pub struct FidgetSettings {
+    /// Seconds a unit has to be idle before it starts fidgeting.
+    pub idle_delay: f32,
+    /// Chance per second of starting a fidget once the delay has passed.
+    pub chance_per_second: f64,
+    /// Time used to blend into and out of a fidget clip.
+    pub transition: Duration,
+}
+
+impl Default for FidgetSettings {
+    fn default() -> Self {
+        Self {
+            idle_delay: 8.0,
+            chance_per_second: 0.15,
+            transition: Duration::from_millis(300),
+        }
+    }
+}
+
+/// Seconds since the unit last moved.
+#[derive(Component, Default)]
+pub struct IdleTime(pub f32);
+
+/// A clip playing once, after which the unit goes back to its idle loop.
+#[derive(Component)]
+pub struct OneShotAnimation {
+    remaining: f32,
+}
+
+/// Finds the unit that owns each newly spawned `AnimationPlayer`
+fn link_animation_players(
+    mut commands: Commands,
+    players: Query<Entity, Added<AnimationPlayer>>,
+    parents: Query<&Parent>,
+    units: Query<(), With<Movable>>,
+) {
+    for player in players.iter() {
+        if let Some(unit) = parents
+            .iter_ancestors(player)
+            .find(|ancestor| units.contains(*ancestor))
+        {
+            commands.entity(unit).insert(AnimationPlayerLink(player));
+        }
+    }
+}
+
+/// Walks while a unit is `Moving` and goes back to the idle loop when it stops.
+fn play_movement_animations(
+    animations: Res<Animations>,
+    started: Query<&AnimationPlayerLink, Added<Moving>>,
+    mut stopped: RemovedComponents<Moving>,
+    links: Query<&AnimationPlayerLink>,
+    mut animation_players: Query<&mut AnimationPlayer>,
+) {
+    for link in started.iter() {
+        if let Ok(mut player) = animation_players.get_mut(link.0) {
+            player.play(animations.0[0].clone_weak()).repeat();
+        }
+    }
+
+    for entity in stopped.iter() {
+        let Ok(link) = links.get(entity) else {
+            continue;
+        };
+        if let Ok(mut player) = animation_players.get_mut(link.0) {
+            player.play(animations.0[1].clone_weak()).repeat();
+        }
+    }
+}
+
+fn track_idle_time(time: Res<Time>, mut units: Query<(&mut IdleTime, Option<&Moving>)>) {
+    for (mut idle_time, moving) in units.iter_mut() {
+        if moving.is_some() {
+            idle_time.0 = 0.0;
+        } else {
+            idle_time.0 += time.delta_seconds();
+        }
+    }
+}
+
+fn play_idle_fidgets(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<FidgetSettings>,
+    fidgets: Res<FidgetAnimations>,
+    clips: Res<Assets<AnimationClip>>,
+    mut units: Query<
+        (Entity, &mut IdleTime, &AnimationPlayerLink),
+        (Without<Moving>, Without<OneShotAnimation>),
+    >,
+    mut animation_players: Query<&mut AnimationPlayer>,
+) {
+    let mut rng = rand::thread_rng();
+    let chance = (settings.chance_per_second * time.delta_seconds_f64()).min(1.0);
+
+    for (entity, mut idle_time, link) in units.iter_mut() {
+        if idle_time.0 < settings.idle_delay || !rng.gen_bool(chance) {
+            continue;
+        }
+
+        let Some(clip) = fidgets.0.choose(&mut rng) else {
+            return;
+        };
+        let Some(duration) = clips.get(clip).map(|clip| clip.duration()) else {
+            continue;
+        };
+        let Ok(mut player) = animation_players.get_mut(link.0) else {
+            continue;
+        };
+
+        player.play_with_transition(clip.clone_weak(), settings.transition);
+        commands.entity(entity).insert(OneShotAnimation {
+            remaining: duration,
+        });
+        idle_time.0 = 0.0;
+    }
+}
+
+/// Returns units to the idle loop once their one-shot clip has finished.
+/// Units that started moving keep the walk animation they were given.
+fn finish_one_shot_animations(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<FidgetSettings>,
+    animations: Res<Animations>,
+    mut units: Query<(
+        Entity,
+        &mut OneShotAnimation,
+        &AnimationPlayerLink,
+        Option<&Moving>,
+    )>,
+    mut animation_players: Query<&mut AnimationPlayer>,
+) {
+    for (entity, mut one_shot, link, moving) in units.iter_mut() {
+        if moving.is_some() {
+            commands.entity(entity).remove::<OneShotAnimation>();
+            continue;
+        }
+
+        one_shot.remaining -= time.delta_seconds();
+        if one_shot.remaining > settings.transition.as_secs_f32() {
+            continue;
+        }
+
+        if let Ok(mut player) = animation_players.get_mut(link.0) {
+            player
+                .play_with_transition(animations.0[1].clone_weak(), settings.transition)
+                .repeat();
+        }
+        commands.entity(entity).remove::<OneShotAnimation>();
+    }
+}
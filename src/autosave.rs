@@ -0,0 +1,147 @@
+//! Autosaving: every few minutes of play the game saves itself in the
+//! background, taking turns between a few autosave files so the oldest is
+//! overwritten each time. The pause menu can load the newest one.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::save::{SaveGame, SaveSlot};
+use crate::Gameplay;
+
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AutosaveConfig>()
+            .init_resource::<AutosaveTimer>()
+            .add_system(autosave.in_set(Gameplay));
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutosaveConfig {
+    pub enabled: bool,
+    /// Minutes of play between autosaves.
+    pub interval_minutes: f32,
+    /// Autosave files taken in turn.
+    pub slots: usize,
+}
+
+impl Default for AutosaveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_minutes: 5.0,
+            slots: 3,
+        }
+    }
+}
+
+impl AutosaveConfig {
+    /// Shortest interval allowed, so a bad settings file can't have the
+    /// game saving every frame.
+    const MIN_INTERVAL_MINUTES: f32 = 0.5;
+
+    /// The config with an interval the timer can use: at least the
+    /// minimum, or the default if it isn't a number at all.
+    pub fn validated(self) -> Self {
+        let interval_minutes = if self.interval_minutes.is_finite() {
+            self.interval_minutes.max(Self::MIN_INTERVAL_MINUTES)
+        } else {
+            Self::default().interval_minutes
+        };
+        Self {
+            interval_minutes,
+            ..self
+        }
+    }
+}
+
+#[derive(Resource)]
+struct AutosaveTimer {
+    timer: Timer,
+    /// Autosave slot written next, worked out at the first autosave so the
+    /// slot count from the settings file is used.
+    next_slot: Option<usize>,
+}
+
+impl Default for AutosaveTimer {
+    fn default() -> Self {
+        // The interval is set from the config as the timer runs
+        Self {
+            timer: Timer::new(Duration::ZERO, TimerMode::Repeating),
+            next_slot: None,
+        }
+    }
+}
+
+/// The autosave slot written most recently, if there are any autosaves.
+pub fn latest_autosave(slots: usize) -> Option<usize> {
+    (0..slots)
+        .filter_map(|slot| {
+            let modified = SaveSlot::Autosave(slot)
+                .path()
+                .metadata()
+                .ok()?
+                .modified()
+                .ok()?;
+            Some((slot, modified))
+        })
+        .max_by_key(|(_, modified)| *modified)
+        .map(|(slot, _)| slot)
+}
+
+/// Counts game time, which stops while paused, and saves to the next slot
+/// whenever the interval is up.
+fn autosave(
+    time: Res<Time>,
+    config: Res<AutosaveConfig>,
+    mut state: ResMut<AutosaveTimer>,
+    mut saves: EventWriter<SaveGame>,
+) {
+    if !config.enabled || config.slots == 0 {
+        return;
+    }
+
+    let interval = Duration::from_secs_f32(config.interval_minutes * 60.0);
+    if state.timer.duration() != interval {
+        state.timer.set_duration(interval);
+    }
+    if !state.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // Carry on after the newest autosave from an earlier game
+    let slot = state
+        .next_slot
+        .unwrap_or_else(|| latest_autosave(config.slots).map_or(0, |slot| slot + 1))
+        % config.slots;
+    saves.send(SaveGame(SaveSlot::Autosave(slot)));
+    state.next_slot = Some((slot + 1) % config.slots);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_intervals_are_replaced() {
+        let interval = |interval_minutes| {
+            AutosaveConfig {
+                interval_minutes,
+                ..default()
+            }
+            .validated()
+            .interval_minutes
+        };
+
+        assert_eq!(interval(10.0), 10.0);
+        assert_eq!(interval(-5.0), AutosaveConfig::MIN_INTERVAL_MINUTES);
+        assert_eq!(interval(0.0), AutosaveConfig::MIN_INTERVAL_MINUTES);
+        assert_eq!(interval(f32::NAN), 5.0);
+        assert_eq!(interval(f32::INFINITY), 5.0);
+    }
+}
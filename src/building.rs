@@ -0,0 +1,786 @@
+//! Construction mode: a build menu, a ghost of the chosen structure that
+//! follows the cursor on the tile grid, and placement of construction sites
+//! that workers then build up over time.
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::MainCamera;
+use crate::chatbox::{GameMessage, MessageCategory};
+use crate::combat::AttackTask;
+use crate::grid::{Footprint, OccupancyGrid};
+use crate::input::{cursor_ray, ground_point, Action, ActionInput};
+use crate::map_file::MapData;
+use crate::notable_events::{NotableEventKind, NotableEvents};
+use crate::terrain::TileMap;
+use crate::ui::{PointerOverUi, Popup, UiFont, WorldAnchor};
+use crate::{Destination, Gameplay, Movable, Moving, Selected};
+
+pub struct BuildingPlugin;
+
+impl Plugin for BuildingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OccupancyGrid>()
+            .init_resource::<BuildMode>()
+            .add_startup_system(setup_build_assets)
+            // After the startup commands that insert the build assets
+            .add_startup_system(spawn_map_buildings.in_base_set(StartupSet::PostStartup))
+            .add_system(toggle_build_menu.in_set(Gameplay))
+            .add_system(choose_building.after(toggle_build_menu).in_set(Gameplay))
+            .add_system(update_ghost.after(choose_building).in_set(Gameplay))
+            .add_event::<PlaceConstructionSite>()
+            .add_system(place_construction_site.after(update_ghost).in_set(Gameplay))
+            .add_system(
+                start_construction_sites
+                    .in_base_set(CoreSet::PostUpdate)
+                    .in_set(Gameplay),
+            )
+            .add_system(spawn_construction_bars)
+            .add_system(
+                process_build_tasks
+                    .in_set(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(update_construction_bars);
+    }
+}
+
+/// How close a worker has to be to a site's footprint to work on it.
+const BUILD_RANGE: f32 = 0.4;
+/// Fraction of the final height a fresh construction site starts at.
+const FOUNDATION_HEIGHT: f32 = 0.2;
+const CONSTRUCTION_BAR_SIZE: Vec2 = Vec2::new(50.0, 5.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BuildingKind {
+    House,
+    Watchtower,
+    Wall,
+}
+
+impl BuildingKind {
+    pub const ALL: [BuildingKind; 3] = [
+        BuildingKind::House,
+        BuildingKind::Watchtower,
+        BuildingKind::Wall,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildingKind::House => "House",
+            BuildingKind::Watchtower => "Watchtower",
+            BuildingKind::Wall => "Wall",
+        }
+    }
+
+    /// Flavour text shown when the structure is examined.
+    pub fn examine(&self) -> &'static str {
+        match self {
+            BuildingKind::House => "A place to call home.",
+            BuildingKind::Watchtower => "Keeps a lookout over the area.",
+            BuildingKind::Wall => "Keeps unwanted visitors out.",
+        }
+    }
+
+    /// Size in tiles.
+    pub fn footprint_size(&self) -> UVec2 {
+        match self {
+            BuildingKind::House => UVec2::new(2, 2),
+            BuildingKind::Watchtower => UVec2::new(1, 1),
+            BuildingKind::Wall => UVec2::new(1, 1),
+        }
+    }
+
+    pub fn height(&self) -> f32 {
+        match self {
+            BuildingKind::House => 1.2,
+            BuildingKind::Watchtower => 2.0,
+            BuildingKind::Wall => 0.6,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            BuildingKind::House => Color::rgb(0.6, 0.45, 0.3),
+            BuildingKind::Watchtower => Color::rgb(0.5, 0.5, 0.55),
+            BuildingKind::Wall => Color::rgb(0.45, 0.45, 0.45),
+        }
+    }
+
+    /// Seconds a single worker needs to finish the structure.
+    pub fn build_time(&self) -> f32 {
+        match self {
+            BuildingKind::House => 20.0,
+            BuildingKind::Watchtower => 30.0,
+            BuildingKind::Wall => 6.0,
+        }
+    }
+}
+
+/// The structure being placed, if construction mode is active.
+#[derive(Resource, Default)]
+pub struct BuildMode {
+    pub placing: Option<BuildingKind>,
+}
+
+/// A placed structure that has not been built yet.
+#[derive(Component)]
+pub struct ConstructionSite {
+    pub kind: BuildingKind,
+    /// Fraction of the work done, from 0 to 1.
+    pub progress: f32,
+}
+
+/// A finished structure.
+#[derive(Component)]
+pub struct Building {
+    pub kind: BuildingKind,
+}
+
+/// Order to walk to a construction site and work on it until it is done.
+#[derive(Component)]
+pub struct BuildTask {
+    pub site: Entity,
+}
+
+/// Finds the construction site covering a point on the ground.
+pub fn construction_site_at<'a>(
+    point: Vec3,
+    sites: impl Iterator<Item = (Entity, &'a Footprint)>,
+) -> Option<Entity> {
+    sites
+        .filter(|(_, footprint)| footprint.contains(point))
+        .map(|(entity, _)| entity)
+        .next()
+}
+
+#[derive(Resource)]
+pub struct BuildAssets {
+    cube: Handle<Mesh>,
+    /// Meshes of the finished structures.
+    buildings: HashMap<BuildingKind, Handle<Mesh>>,
+    valid_ghost: Handle<StandardMaterial>,
+    invalid_ghost: Handle<StandardMaterial>,
+    site: Handle<StandardMaterial>,
+    finished: HashMap<BuildingKind, Handle<StandardMaterial>>,
+}
+
+#[derive(Component)]
+struct ConstructionBar {
+    site: Entity,
+}
+
+#[derive(Component)]
+struct ConstructionBarFill;
+
+#[derive(Component)]
+struct BuildMenu;
+
+#[derive(Component)]
+struct BuildMenuButton(BuildingKind);
+
+/// Translucent preview of the structure under the cursor.
+#[derive(Component)]
+struct Ghost {
+    kind: BuildingKind,
+    valid: bool,
+}
+
+fn setup_build_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let ghost_material = |color: Color| StandardMaterial {
+        base_color: color,
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    };
+
+    commands.insert_resource(BuildAssets {
+        cube: meshes.add(shape::Cube { size: 1.0 }.into()),
+        buildings: BuildingKind::ALL
+            .iter()
+            .map(|kind| (*kind, meshes.add(building_mesh(*kind))))
+            .collect(),
+        valid_ghost: materials.add(ghost_material(Color::rgba(0.2, 0.9, 0.2, 0.4))),
+        invalid_ghost: materials.add(ghost_material(Color::rgba(0.9, 0.1, 0.1, 0.4))),
+        site: materials.add(Color::rgb(0.55, 0.4, 0.25).into()),
+        finished: BuildingKind::ALL
+            .iter()
+            .map(|kind| (*kind, materials.add(kind.color().into())))
+            .collect(),
+    });
+}
+
+/// A box `size` big standing on the ground at `position`.
+fn ground_box(size: Vec3, position: Vec3) -> shape::Box {
+    shape::Box {
+        min_x: position.x - size.x / 2.0,
+        max_x: position.x + size.x / 2.0,
+        min_y: position.y,
+        max_y: position.y + size.y,
+        min_z: position.z - size.z / 2.0,
+        max_z: position.z + size.z / 2.0,
+    }
+}
+
+/// The finished look of a structure, built from boxes around the middle of
+/// its footprint at ground level.
+fn building_mesh(kind: BuildingKind) -> Mesh {
+    let size = kind.footprint_size().as_vec2();
+    let height = kind.height();
+    let parts = match kind {
+        // Walls under a roof that steps in towards its ridge
+        BuildingKind::House => vec![
+            ground_box(
+                Vec3::new(size.x * 0.9, height * 0.6, size.y * 0.9),
+                Vec3::ZERO,
+            ),
+            ground_box(
+                Vec3::new(size.x, height * 0.2, size.y * 0.7),
+                Vec3::Y * height * 0.6,
+            ),
+            ground_box(
+                Vec3::new(size.x, height * 0.2, size.y * 0.35),
+                Vec3::Y * height * 0.8,
+            ),
+        ],
+        // A lookout platform on four legs, with a little roof
+        BuildingKind::Watchtower => {
+            let leg = Vec3::new(0.12, height * 0.75, 0.12);
+            let mut parts: Vec<_> = [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)]
+                .into_iter()
+                .map(|(x, z)| ground_box(leg, Vec3::new(x * size.x, 0.0, z * size.y) * 0.35))
+                .collect();
+            parts.push(ground_box(
+                Vec3::new(size.x * 0.9, height * 0.08, size.y * 0.9),
+                Vec3::Y * height * 0.75,
+            ));
+            parts.push(ground_box(
+                Vec3::new(size.x * 0.6, height * 0.17, size.y * 0.6),
+                Vec3::Y * height * 0.83,
+            ));
+            parts
+        }
+        // Battlements along the top
+        BuildingKind::Wall => vec![
+            ground_box(Vec3::new(size.x, height * 0.75, size.y * 0.4), Vec3::ZERO),
+            ground_box(
+                Vec3::new(size.x * 0.3, height * 0.25, size.y * 0.4),
+                Vec3::new(-size.x * 0.3, height * 0.75, 0.0),
+            ),
+            ground_box(
+                Vec3::new(size.x * 0.3, height * 0.25, size.y * 0.4),
+                Vec3::new(size.x * 0.3, height * 0.75, 0.0),
+            ),
+        ],
+    };
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for part in parts {
+        let part = Mesh::from(part);
+        let first = positions.len() as u32;
+        if let Some(VertexAttributeValues::Float32x3(values)) =
+            part.attribute(Mesh::ATTRIBUTE_POSITION)
+        {
+            positions.extend(values);
+        }
+        if let Some(VertexAttributeValues::Float32x3(values)) =
+            part.attribute(Mesh::ATTRIBUTE_NORMAL)
+        {
+            normals.extend(values);
+        }
+        if let Some(VertexAttributeValues::Float32x2(values)) = part.attribute(Mesh::ATTRIBUTE_UV_0)
+        {
+            uvs.extend(values);
+        }
+        if let Some(Indices::U32(part_indices)) = part.indices() {
+            indices.extend(part_indices.iter().map(|index| first + index));
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Puts up the finished structures the map starts with.
+pub fn spawn_map_buildings(
+    mut commands: Commands,
+    map: Res<MapData>,
+    tile_map: Res<TileMap>,
+    assets: Res<BuildAssets>,
+    mut occupancy: ResMut<OccupancyGrid>,
+) {
+    for building in map.buildings.iter() {
+        let footprint = Footprint {
+            min: building.tile.into(),
+            size: building.kind.footprint_size(),
+        };
+        spawn_building(
+            &mut commands,
+            &assets,
+            &tile_map,
+            &mut occupancy,
+            building.kind,
+            footprint,
+        );
+    }
+}
+
+/// Spawns a finished structure standing on the ground and takes its tiles.
+pub fn spawn_building(
+    commands: &mut Commands,
+    assets: &BuildAssets,
+    tile_map: &TileMap,
+    occupancy: &mut OccupancyGrid,
+    kind: BuildingKind,
+    footprint: Footprint,
+) -> Entity {
+    occupancy.occupy(footprint.tiles());
+    commands
+        .spawn((
+            PbrBundle {
+                mesh: assets.buildings[&kind].clone(),
+                material: assets.finished[&kind].clone(),
+                transform: Transform::from_translation(
+                    tile_map.ground_position(footprint.center()),
+                ),
+                ..default()
+            },
+            footprint,
+            Building { kind },
+        ))
+        .id()
+}
+
+/// B opens and closes the build menu, Escape leaves construction mode.
+fn toggle_build_menu(
+    mut commands: Commands,
+    actions: ActionInput,
+    font: Res<UiFont>,
+    mut build_mode: ResMut<BuildMode>,
+    menus: Query<Entity, With<BuildMenu>>,
+) {
+    let close = actions.just_pressed(Action::Cancel)
+        || (actions.just_pressed(Action::BuildMenu) && !menus.is_empty());
+
+    if close {
+        for menu in menus.iter() {
+            commands.entity(menu).despawn_recursive();
+        }
+        build_mode.placing = None;
+        return;
+    }
+
+    if !actions.just_pressed(Action::BuildMenu) {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.0),
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    gap: Size::all(Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            },
+            Interaction::default(),
+            BuildMenu,
+            Popup,
+        ))
+        .with_children(|menu| {
+            for kind in BuildingKind::ALL {
+                menu.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(6.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.25, 0.25, 0.25).into(),
+                        ..default()
+                    },
+                    BuildMenuButton(kind),
+                ))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(
+                        kind.name(),
+                        TextStyle {
+                            font: font.0.clone(),
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                });
+            }
+        });
+}
+
+fn choose_building(
+    mut commands: Commands,
+    mut build_mode: ResMut<BuildMode>,
+    buttons: Query<(&Interaction, &BuildMenuButton), Changed<Interaction>>,
+    menus: Query<Entity, With<BuildMenu>>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            build_mode.placing = Some(button.0);
+            for menu in menus.iter() {
+                commands.entity(menu).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Moves the ghost to the grid position under the cursor and colours it by
+/// whether the structure could be placed there.
+fn update_ghost(
+    mut commands: Commands,
+    build_mode: Res<BuildMode>,
+    assets: Res<BuildAssets>,
+    occupancy: Res<OccupancyGrid>,
+    tile_map: Res<TileMap>,
+    windows: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut ghosts: Query<(
+        Entity,
+        &mut Ghost,
+        &mut Transform,
+        &mut Footprint,
+        &mut Handle<StandardMaterial>,
+    )>,
+) {
+    let Some(kind) = build_mode.placing else {
+        for (entity, ..) in ghosts.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+
+    let (camera, camera_transform) = camera.single();
+    let Some(point) = cursor_ray(windows.single(), camera, camera_transform)
+        .and_then(|ray| ground_point(ray, &tile_map))
+    else {
+        return;
+    };
+
+    let footprint = Footprint::centered_at(point, kind.footprint_size());
+    let valid = footprint
+        .tiles()
+        .all(|tile| tile_map.is_walkable(tile) && !occupancy.is_occupied(tile));
+    let size = kind.footprint_size().as_vec2();
+    let transform = Transform::from_translation(
+        tile_map.ground_position(footprint.center()) + Vec3::Y * kind.height() / 2.0,
+    )
+    .with_scale(Vec3::new(size.x, kind.height(), size.y));
+    let material = if valid {
+        assets.valid_ghost.clone()
+    } else {
+        assets.invalid_ghost.clone()
+    };
+
+    match ghosts.iter_mut().find(|(_, ghost, ..)| ghost.kind == kind) {
+        Some((_, mut ghost, mut ghost_transform, mut ghost_footprint, mut ghost_material)) => {
+            ghost.valid = valid;
+            *ghost_transform = transform;
+            *ghost_footprint = footprint;
+            *ghost_material = material;
+        }
+        None => {
+            for (entity, ..) in ghosts.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            commands.spawn((
+                PbrBundle {
+                    mesh: assets.cube.clone(),
+                    material,
+                    transform,
+                    ..default()
+                },
+                Ghost { kind, valid },
+                footprint,
+            ));
+        }
+    }
+}
+
+/// The player placing a construction site and sending `builders` to build
+/// it.
+#[derive(Clone, Debug)]
+pub struct PlaceConstructionSite {
+    pub kind: BuildingKind,
+    pub footprint: Footprint,
+    pub builders: Vec<Entity>,
+}
+
+/// Left click places a construction site where the ghost is, if the spot is
+/// free, and sends the selected units to build it. Holding Shift keeps
+/// construction mode on for placing more.
+fn place_construction_site(
+    mouse_button_input: Res<Input<MouseButton>>,
+    actions: ActionInput,
+    pointer_over_ui: Res<PointerOverUi>,
+    mut build_mode: ResMut<BuildMode>,
+    ghosts: Query<(&Ghost, &Footprint)>,
+    workers: Query<Entity, (With<Selected>, With<Movable>)>,
+    mut placements: EventWriter<PlaceConstructionSite>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    if !mouse_button_input.just_pressed(MouseButton::Left) || pointer_over_ui.0 {
+        return;
+    }
+    let Some(kind) = build_mode.placing else {
+        return;
+    };
+    let Some((ghost, footprint)) = ghosts.iter().find(|(ghost, _)| ghost.kind == kind) else {
+        return;
+    };
+    if !ghost.valid {
+        messages.send(GameMessage::new(
+            MessageCategory::Error,
+            "You can't build there.",
+        ));
+        return;
+    }
+
+    placements.send(PlaceConstructionSite {
+        kind,
+        footprint: *footprint,
+        builders: workers.iter().collect(),
+    });
+
+    if !actions.pressed(Action::AddModifier) {
+        build_mode.placing = None;
+    }
+}
+
+/// Spawns the construction sites placed this frame and sets their builders
+/// to work. Sites whose tiles have been taken since are skipped.
+pub fn start_construction_sites(
+    mut commands: Commands,
+    mut placements: EventReader<PlaceConstructionSite>,
+    assets: Res<BuildAssets>,
+    mut occupancy: ResMut<OccupancyGrid>,
+    tile_map: Res<TileMap>,
+    workers: Query<(), With<Movable>>,
+) {
+    for placement in placements.iter() {
+        if placement
+            .footprint
+            .tiles()
+            .any(|tile| occupancy.is_occupied(tile))
+        {
+            continue;
+        }
+
+        let site = spawn_construction_site(
+            &mut commands,
+            &assets,
+            &tile_map,
+            &mut occupancy,
+            placement.kind,
+            placement.footprint,
+            0.0,
+        );
+        for &worker in placement.builders.iter() {
+            if workers.contains(worker) {
+                commands
+                    .entity(worker)
+                    .insert(BuildTask { site })
+                    .remove::<AttackTask>();
+            }
+        }
+        info!("Placed a {} construction site", placement.kind.name());
+    }
+}
+
+/// Spawns a construction site `progress` of the way built and takes its
+/// tiles.
+pub fn spawn_construction_site(
+    commands: &mut Commands,
+    assets: &BuildAssets,
+    tile_map: &TileMap,
+    occupancy: &mut OccupancyGrid,
+    kind: BuildingKind,
+    footprint: Footprint,
+    progress: f32,
+) -> Entity {
+    let size = kind.footprint_size().as_vec2();
+    let height = site_height(kind, progress);
+    occupancy.occupy(footprint.tiles());
+    commands
+        .spawn((
+            PbrBundle {
+                mesh: assets.cube.clone(),
+                material: assets.site.clone(),
+                transform: Transform::from_translation(
+                    tile_map.ground_position(footprint.center()) + Vec3::Y * height / 2.0,
+                )
+                .with_scale(Vec3::new(size.x, height, size.y)),
+                ..default()
+            },
+            ConstructionSite { kind, progress },
+            footprint,
+        ))
+        .id()
+}
+
+/// Height of a construction site, which starts as a low foundation of the
+/// final structure and grows towards its full height.
+fn site_height(kind: BuildingKind, progress: f32) -> f32 {
+    kind.height() * (FOUNDATION_HEIGHT + (1.0 - FOUNDATION_HEIGHT) * progress.min(1.0))
+}
+
+/// Walks builders to their site and advances construction. Each extra worker
+/// helps less than the one before: n workers build 3n / (n + 2) times as
+/// fast as one. A finished site is replaced by the finished structure.
+fn process_build_tasks(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    assets: Res<BuildAssets>,
+    tile_map: Res<TileMap>,
+    mut occupancy: ResMut<OccupancyGrid>,
+    mut notable_events: ResMut<NotableEvents>,
+    mut messages: EventWriter<GameMessage>,
+    builders: Query<(Entity, &Transform, &BuildTask, Option<&Destination>), Without<Footprint>>,
+    mut sites: Query<(Entity, &mut ConstructionSite, &Footprint, &mut Transform)>,
+) {
+    let mut workers_at_site: HashMap<Entity, u32> = HashMap::new();
+
+    for (entity, transform, task, destination) in builders.iter() {
+        let Ok((_, _, footprint, ..)) = sites.get(task.site) else {
+            commands.entity(entity).remove::<BuildTask>();
+            continue;
+        };
+
+        let target = footprint.closest_point(transform.translation);
+        let mut offset = target - transform.translation;
+        offset.y = 0.0;
+
+        if offset.length() > BUILD_RANGE {
+            if destination.map_or(true, |destination| destination.0 != target) {
+                commands
+                    .entity(entity)
+                    .insert((Moving {}, Destination(target)));
+            }
+            continue;
+        }
+
+        if destination.is_some() {
+            commands.entity(entity).remove::<(Moving, Destination)>();
+        }
+        *workers_at_site.entry(task.site).or_default() += 1;
+    }
+
+    for (entity, mut site, footprint, mut transform) in sites.iter_mut() {
+        let Some(workers) = workers_at_site.get(&entity) else {
+            continue;
+        };
+
+        let workers = *workers as f32;
+        let speed = 3.0 * workers / (workers + 2.0);
+        site.progress += speed * fixed_time.period.as_secs_f32() / site.kind.build_time();
+
+        let height = site_height(site.kind, site.progress);
+        transform.translation.y = tile_map.height_at(footprint.center()) + height / 2.0;
+        transform.scale.y = height;
+
+        if site.progress >= 1.0 {
+            messages.send(GameMessage::new(
+                MessageCategory::Game,
+                format!("You finish building a {}.", site.kind.name()),
+            ));
+            notable_events.push(NotableEventKind::BuildingFinished, footprint.center());
+            commands.entity(entity).despawn_recursive();
+            spawn_building(
+                &mut commands,
+                &assets,
+                &tile_map,
+                &mut occupancy,
+                site.kind,
+                *footprint,
+            );
+        }
+    }
+}
+
+fn spawn_construction_bars(
+    mut commands: Commands,
+    new_sites: Query<(Entity, &ConstructionSite), Added<ConstructionSite>>,
+) {
+    for (site, construction) in new_sites.iter() {
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(
+                            Val::Px(CONSTRUCTION_BAR_SIZE.x),
+                            Val::Px(CONSTRUCTION_BAR_SIZE.y),
+                        ),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+                    ..default()
+                },
+                ConstructionBar { site },
+                WorldAnchor {
+                    target: site,
+                    offset: Vec3::Y * construction.kind.height(),
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.9, 0.75, 0.2).into(),
+                        ..default()
+                    },
+                    ConstructionBarFill,
+                ));
+            });
+    }
+}
+
+fn update_construction_bars(
+    mut commands: Commands,
+    bars: Query<(Entity, &ConstructionBar, &Children)>,
+    sites: Query<&ConstructionSite>,
+    mut fills: Query<&mut Style, With<ConstructionBarFill>>,
+) {
+    for (entity, bar, children) in bars.iter() {
+        let Ok(site) = sites.get(bar.site) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        for child in children.iter() {
+            if let Ok(mut style) = fills.get_mut(*child) {
+                style.size.width = Val::Percent(site.progress.min(1.0) * 100.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,566 @@
+//! The RTS camera: an isometric orthographic view that scrolls with the
+//! arrow keys, by pushing the cursor against the window edge or by dragging
+//! with the middle mouse button, zooms with the mouse wheel and turns in 90°
+//! steps around the point it looks at. It never looks further off the map
+//! than a small margin.
+//!
+//! Programmatic moves (`CameraJump`) glide to their target instead of
+//! teleporting. Ctrl+F1–F4 bookmark the current view and F1–F4 return to it.
+//! `ShakeCamera` events add a short shake on top of all of this.
+
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy_mod_picking::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::input::{Action, ActionInput};
+use crate::profiling::SystemTimings;
+use crate::terrain::TileMap;
+use crate::ui::PointerOverUi;
+use crate::{MapView, Selected};
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraSettings>()
+            .init_resource::<CameraRotation>()
+            .init_resource::<CameraTween>()
+            .init_resource::<CameraBookmarks>()
+            .init_resource::<CameraShake>()
+            .add_event::<CameraJump>()
+            .add_event::<ShakeCamera>()
+            .add_startup_system(spawn_camera)
+            .add_startup_system(compute_camera_bounds)
+            // A loaded game can bring a different map
+            .add_system(compute_camera_bounds.run_if(resource_changed::<TileMap>()))
+            .configure_set(CameraMovement.in_set(MapView))
+            .add_systems(
+                (
+                    keyboard_camera_movement,
+                    mouse_camera_movement,
+                    drag_camera,
+                    rotate_camera,
+                    tween_camera.after(start_camera_jumps),
+                )
+                    .in_set(CameraMovement),
+            )
+            .add_system(camera_zoom.in_set(MapView))
+            .add_system(center_on_selection.in_set(MapView))
+            .add_system(camera_bookmarks.in_set(MapView))
+            .add_system(
+                start_camera_jumps
+                    .after(center_on_selection)
+                    .after(camera_bookmarks)
+                    .in_set(MapView),
+            )
+            .add_system(remove_camera_shake.before(CameraMovement).in_set(MapView))
+            .add_system(clamp_camera_to_bounds.after(CameraMovement).in_set(MapView))
+            .add_system(
+                apply_camera_shake
+                    .after(clamp_camera_to_bounds)
+                    .in_set(MapView),
+            );
+    }
+}
+
+/// The camera the player looks at the world through, as opposed to the
+/// minimap's.
+#[derive(Component)]
+pub struct MainCamera;
+
+/// Systems that move the camera, which the bounds are enforced after.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct CameraMovement;
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Orthographic scale the camera starts at. Scroll speeds are tuned for
+    /// this zoom level and scale with it.
+    pub default_zoom: f32,
+    /// Closest the camera can zoom in.
+    pub min_zoom: f32,
+    /// Furthest the camera can zoom out.
+    pub max_zoom: f32,
+    /// Fraction of the zoom level changed per mouse wheel step.
+    pub zoom_speed: f32,
+    /// Distance moved per frame while an arrow key is held.
+    pub keyboard_speed: f32,
+    /// Whether pushing the cursor against the window edge scrolls.
+    pub edge_scroll: bool,
+    /// Fraction of the window size, measured from each edge, in which the
+    /// cursor scrolls the camera.
+    pub edge_scroll_margin: f32,
+    /// Distance moved per cursor movement near the window edge.
+    pub edge_scroll_speed: f32,
+    /// Radians per second the camera turns at after Q or E.
+    pub rotation_speed: f32,
+    /// Seconds a `CameraJump` takes to reach its target.
+    pub transition_duration: f32,
+    /// Maps the linear progress of a jump (0 to 1) to how far along the
+    /// camera should be.
+    #[serde(skip)]
+    pub transition_easing: fn(f32) -> f32,
+    /// How far past the edge of the map the camera may look, in world units.
+    pub bounds_margin: f32,
+    /// Largest distance the camera is knocked off its position by a shake
+    /// at full strength.
+    pub shake_amplitude: f32,
+    /// Shake strength lost per second.
+    pub shake_falloff: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            default_zoom: 5.0,
+            min_zoom: 2.0,
+            max_zoom: 10.0,
+            zoom_speed: 0.05,
+            keyboard_speed: 0.1,
+            edge_scroll: true,
+            edge_scroll_margin: 0.02,
+            edge_scroll_speed: 0.1,
+            rotation_speed: 4.0,
+            transition_duration: 0.5,
+            transition_easing: ease_in_out_cubic,
+            bounds_margin: 2.0,
+            shake_amplitude: 0.15,
+            shake_falloff: 2.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    /// How much faster than at the default zoom the camera should scroll, so
+    /// panning covers the same share of the screen at any zoom level.
+    fn speed_factor(&self, projection: &Projection) -> f32 {
+        match projection {
+            Projection::Orthographic(orthographic) => orthographic.scale / self.default_zoom,
+            Projection::Perspective(_) => 1.0,
+        }
+    }
+}
+
+/// Rotation the camera still has to turn through, in radians around the Y
+/// axis. Q and E add a quarter turn each, so quick presses queue up.
+#[derive(Resource, Default)]
+pub struct CameraRotation {
+    remaining: f32,
+}
+
+/// Moves the camera smoothly until it looks at the given point on the ground.
+/// A new jump replaces one that is still in progress.
+pub struct CameraJump(pub Vec3);
+
+#[derive(Resource, Default)]
+struct CameraTween(Option<Tween>);
+
+struct Tween {
+    /// Total distance the camera moves over the whole jump.
+    offset: Vec3,
+    elapsed: f32,
+}
+
+pub fn ease_in_out_cubic(t: f32) -> f32 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
+
+/// Shakes the camera with the given strength, from 0 to 1. Shakes add up, up
+/// to full strength.
+pub struct ShakeCamera(pub f32);
+
+/// Current shake strength and the offset it added to the camera last frame.
+///
+/// The offset is taken off again before the camera moves and put back after
+/// it has been clamped, so a shake never fights the bounds or a jump.
+#[derive(Resource, Default)]
+struct CameraShake {
+    strength: f32,
+    offset: Vec3,
+}
+
+/// Area of the ground the camera's focus point is kept inside, on the XZ
+/// plane.
+#[derive(Resource)]
+pub struct CameraBounds(pub Rect);
+
+pub const BOOKMARK_COUNT: usize = 4;
+
+/// Saved camera focus points, one per bookmark key. Kept in the settings
+/// file, so they last between games.
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct CameraBookmarks(pub [Option<Vec3>; BOOKMARK_COUNT]);
+
+/// The point on the ground the camera is looking at.
+pub fn camera_focus(transform: &Transform) -> Vec3 {
+    let forward = transform.forward();
+    if forward.y.abs() < f32::EPSILON {
+        return transform.translation;
+    }
+    let distance = -transform.translation.y / forward.y;
+    transform.translation + forward * distance
+}
+
+/// World units covered by one logical pixel of the window at the camera's
+/// current zoom.
+fn world_units_per_pixel(projection: &Projection, window: &Window) -> f32 {
+    match projection {
+        Projection::Orthographic(orthographic) => {
+            orthographic.area.height() / window.resolution.height()
+        }
+        Projection::Perspective(_) => 0.0,
+    }
+}
+
+fn spawn_camera(mut commands: Commands, settings: Res<CameraSettings>) {
+    commands.spawn((
+        Camera3dBundle {
+            projection: OrthographicProjection {
+                scale: settings.default_zoom,
+                scaling_mode: ScalingMode::FixedVertical(2.0),
+                ..default()
+            }
+            .into(),
+            transform: Transform::from_xyz(5.0, 5.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        },
+        RaycastPickCamera::default(),
+        MainCamera,
+    ));
+}
+
+fn compute_camera_bounds(
+    mut commands: Commands,
+    settings: Res<CameraSettings>,
+    tile_map: Res<TileMap>,
+) {
+    let map = tile_map.bounds();
+    let margin = Vec2::splat(settings.bounds_margin);
+    commands.insert_resource(CameraBounds(Rect::from_corners(
+        map.min - margin,
+        map.max + margin,
+    )));
+}
+
+fn keyboard_camera_movement(
+    actions: ActionInput,
+    settings: Res<CameraSettings>,
+    mut camera: Query<(&mut Transform, &Projection), With<MainCamera>>,
+) {
+    for (mut transform, projection) in camera.iter_mut() {
+        let mut translation = Vec3::ZERO;
+        if actions.pressed(Action::CameraLeft) {
+            translation -= transform.rotation * Vec3::X;
+        }
+        if actions.pressed(Action::CameraRight) {
+            translation += transform.rotation * Vec3::X;
+        }
+        if actions.pressed(Action::CameraUp) {
+            translation += transform.rotation * Vec3::Y;
+        }
+        if actions.pressed(Action::CameraDown) {
+            translation -= transform.rotation * Vec3::Y;
+        }
+        transform.translation +=
+            translation * settings.keyboard_speed * settings.speed_factor(projection);
+    }
+}
+
+fn mouse_camera_movement(
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut camera: Query<(&mut Transform, &Projection), With<MainCamera>>,
+    mut windows: Query<&mut Window>,
+    settings: Res<CameraSettings>,
+    pointer_over_ui: Res<PointerOverUi>,
+    timings: Res<SystemTimings>,
+) {
+    let _scope = timings.scope("mouse_camera_movement");
+
+    for event in cursor_moved_events.iter() {
+        if !settings.edge_scroll || pointer_over_ui.0 {
+            continue;
+        }
+
+        // Camera needs to move when the mouse is near the edge of the screen
+        // 1) First we need to get the size of the window
+        let window = windows.single_mut();
+        if !window.focused {
+            continue;
+        }
+        let scale_factor = window.resolution.scale_factor() as f32;
+        let physical_width = window.resolution.physical_width() as f32;
+        let physical_height = window.resolution.physical_height() as f32;
+
+        let actual_resolution = Vec2::new(
+            physical_width / scale_factor,
+            physical_height / scale_factor,
+        );
+
+        // 2) Then we need to get the mouse position
+        let mouse_position = event.position;
+
+        // 3) Then we need to get the center of the screen
+        let center = actual_resolution / 2.0;
+
+        // 4) Then we need to get the difference between the mouse position and the center
+        let difference = mouse_position - center;
+
+        // 5) Then we need to scale the difference based on the size of the window
+        let scaled_difference = difference / actual_resolution;
+
+        // 6) Then we need to move the camera based on the difference
+        // 6.1) Only move the camera if the mouse is near the edge of the screen
+        let threshold = 0.5 - settings.edge_scroll_margin;
+        if scaled_difference.x.abs() > threshold || scaled_difference.y.abs() > threshold {
+            for (mut transform, projection) in camera.iter_mut() {
+                // 6.2) Move relative to the camera so it still works when rotated
+                let speed = settings.edge_scroll_speed * settings.speed_factor(projection);
+                let translation = transform.rotation * scaled_difference.extend(0.0);
+                transform.translation += translation * speed;
+            }
+        }
+    }
+}
+
+/// Holding the middle mouse button and dragging pans the camera so the ground
+/// under the cursor follows it.
+fn drag_camera(
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    mut camera: Query<(&mut Transform, &Projection), With<MainCamera>>,
+    windows: Query<&Window>,
+    pointer_over_ui: Res<PointerOverUi>,
+    mut dragging: Local<bool>,
+) {
+    if mouse_button_input.just_pressed(MouseButton::Middle) {
+        *dragging = !pointer_over_ui.0;
+    }
+    if !mouse_button_input.pressed(MouseButton::Middle) {
+        *dragging = false;
+    }
+
+    let delta: Vec2 = mouse_motion_events.iter().map(|event| event.delta).sum();
+    if !*dragging || delta == Vec2::ZERO {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    for (mut transform, projection) in camera.iter_mut() {
+        // Mouse motion is in screen space, where y points down
+        let scale = world_units_per_pixel(projection, window);
+        let translation = transform.rotation * Vec3::new(-delta.x, delta.y, 0.0);
+        transform.translation += translation * scale;
+    }
+}
+
+/// Zooms the orthographic projection with the mouse wheel, between the
+/// configured limits.
+fn camera_zoom(
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut camera: Query<&mut Projection, With<MainCamera>>,
+    settings: Res<CameraSettings>,
+    pointer_over_ui: Res<PointerOverUi>,
+) {
+    for event in mouse_wheel_events.iter() {
+        if pointer_over_ui.0 {
+            continue;
+        }
+
+        for mut projection in camera.iter_mut() {
+            if let Projection::Orthographic(orthographic) = projection.as_mut() {
+                orthographic.scale = (orthographic.scale * (1.0 - event.y * settings.zoom_speed))
+                    .clamp(settings.min_zoom, settings.max_zoom);
+            }
+        }
+    }
+}
+
+/// Q and E turn the camera a quarter turn around its focus point, easing the
+/// turn over a few frames instead of snapping.
+fn rotate_camera(
+    actions: ActionInput,
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    mut rotation: ResMut<CameraRotation>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    if actions.just_pressed(Action::RotateCameraLeft) {
+        rotation.remaining -= FRAC_PI_2;
+    }
+    if actions.just_pressed(Action::RotateCameraRight) {
+        rotation.remaining += FRAC_PI_2;
+    }
+    if rotation.remaining == 0.0 {
+        return;
+    }
+
+    let max_step = settings.rotation_speed * time.delta_seconds();
+    let step = rotation.remaining.clamp(-max_step, max_step);
+    rotation.remaining -= step;
+
+    for mut transform in camera.iter_mut() {
+        let focus = camera_focus(&transform);
+        transform.rotate_around(focus, Quat::from_rotation_y(step));
+    }
+}
+
+/// Home jumps the camera to the middle of the current selection.
+fn center_on_selection(
+    actions: ActionInput,
+    selected: Query<&GlobalTransform, With<Selected>>,
+    mut jumps: EventWriter<CameraJump>,
+) {
+    if !actions.just_pressed(Action::CenterOnSelection) {
+        return;
+    }
+
+    let (sum, count) = selected
+        .iter()
+        .fold((Vec3::ZERO, 0), |(sum, count), transform| {
+            (sum + transform.translation(), count + 1)
+        });
+    if count > 0 {
+        jumps.send(CameraJump(sum / count as f32));
+    }
+}
+
+fn start_camera_jumps(
+    mut jumps: EventReader<CameraJump>,
+    mut tween: ResMut<CameraTween>,
+    camera: Query<&Transform, With<MainCamera>>,
+) {
+    let Some(jump) = jumps.iter().last() else {
+        return;
+    };
+    let Ok(transform) = camera.get_single() else {
+        return;
+    };
+
+    let mut offset = jump.0 - camera_focus(transform);
+    offset.y = 0.0;
+    tween.0 = Some(Tween {
+        offset,
+        elapsed: 0.0,
+    });
+}
+
+/// Advances the current jump. The camera is moved by how much the eased
+/// progress changed this frame rather than set to an absolute position, so
+/// scrolling by hand during a jump still works.
+fn tween_camera(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    mut tween: ResMut<CameraTween>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Some(current) = tween.0.as_mut() else {
+        return;
+    };
+
+    let duration = settings.transition_duration.max(f32::EPSILON);
+    let before = (settings.transition_easing)((current.elapsed / duration).min(1.0));
+    current.elapsed += time.delta_seconds();
+    let after = (settings.transition_easing)((current.elapsed / duration).min(1.0));
+
+    for mut transform in camera.iter_mut() {
+        transform.translation += current.offset * (after - before);
+    }
+
+    if current.elapsed >= duration {
+        tween.0 = None;
+    }
+}
+
+fn camera_bookmarks(
+    actions: ActionInput,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    camera: Query<&Transform, With<MainCamera>>,
+    mut jumps: EventWriter<CameraJump>,
+) {
+    let control = actions.pressed(Action::ControlModifier);
+
+    for slot in 0..BOOKMARK_COUNT {
+        if !actions.just_pressed(Action::CameraBookmark(slot)) {
+            continue;
+        }
+
+        if control {
+            let Ok(transform) = camera.get_single() else {
+                continue;
+            };
+            bookmarks.0[slot] = Some(camera_focus(transform));
+        } else if let Some(focus) = bookmarks.0[slot] {
+            jumps.send(CameraJump(focus));
+        }
+    }
+}
+
+/// Pulls the camera back so its focus point stays inside `CameraBounds`.
+fn clamp_camera_to_bounds(
+    bounds: Option<Res<CameraBounds>>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Some(bounds) = bounds else {
+        return;
+    };
+
+    for mut transform in camera.iter_mut() {
+        let focus = camera_focus(&transform);
+        let clamped = Vec2::new(focus.x, focus.z).clamp(bounds.0.min, bounds.0.max);
+        transform.translation += Vec3::new(clamped.x - focus.x, 0.0, clamped.y - focus.z);
+    }
+}
+
+fn remove_camera_shake(
+    mut shake: ResMut<CameraShake>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    if shake.offset == Vec3::ZERO {
+        return;
+    }
+
+    for mut transform in camera.iter_mut() {
+        transform.translation -= shake.offset;
+    }
+    shake.offset = Vec3::ZERO;
+}
+
+fn apply_camera_shake(
+    mut shake_events: EventReader<ShakeCamera>,
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    mut shake: ResMut<CameraShake>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    for event in shake_events.iter() {
+        shake.strength = (shake.strength + event.0).min(1.0);
+    }
+    if shake.strength <= 0.0 {
+        return;
+    }
+
+    // Squaring makes weak shakes subtle and strong ones fade out quickly
+    let mut rng = rand::thread_rng();
+    let distance = settings.shake_amplitude * shake.strength * shake.strength;
+    let direction = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+
+    for mut transform in camera.iter_mut() {
+        let offset = transform.rotation * (direction * distance).extend(0.0);
+        transform.translation += offset;
+        shake.offset = offset;
+    }
+
+    shake.strength = (shake.strength - settings.shake_falloff * time.delta_seconds()).max(0.0);
+}
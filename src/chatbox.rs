@@ -0,0 +1,310 @@
+//! The chatbox: a message log in the bottom-left corner for player-facing
+//! feedback. Any system can send a `GameMessage` instead of logging with
+//! `info!`. The tabs along the top switch message categories on and off,
+//! and scrolling over the log shows older messages.
+
+use std::collections::VecDeque;
+
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::tooltip::Tooltip;
+use crate::ui::UiFont;
+
+pub struct ChatboxPlugin;
+
+impl Plugin for ChatboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GameMessage>()
+            .init_resource::<ChatLog>()
+            .add_startup_system(spawn_chatbox)
+            .add_system(record_game_messages)
+            .add_system(toggle_chat_filters)
+            .add_system(scroll_chatbox)
+            .add_system(
+                update_chatbox_text
+                    .after(record_game_messages)
+                    .after(toggle_chat_filters)
+                    .after(scroll_chatbox),
+            );
+    }
+}
+
+const CHATBOX_SIZE: Vec2 = Vec2::new(420.0, 170.0);
+/// Distance from the bottom of the window, leaving room for the idle worker
+/// badge underneath.
+const CHATBOX_BOTTOM: f32 = 50.0;
+const VISIBLE_LINES: usize = 8;
+const MAX_MESSAGES: usize = 200;
+const CHAT_FONT_SIZE: f32 = 13.0;
+const TAB_COLOR: Color = Color::rgb(0.25, 0.22, 0.18);
+const TAB_OFF_COLOR: Color = Color::rgb(0.12, 0.11, 0.09);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MessageCategory {
+    Game,
+    Combat,
+    Examine,
+    Error,
+}
+
+impl MessageCategory {
+    const ALL: [MessageCategory; 4] = [
+        MessageCategory::Game,
+        MessageCategory::Combat,
+        MessageCategory::Examine,
+        MessageCategory::Error,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            MessageCategory::Game => "Game",
+            MessageCategory::Combat => "Combat",
+            MessageCategory::Examine => "Examine",
+            MessageCategory::Error => "Errors",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            MessageCategory::Game => Color::WHITE,
+            MessageCategory::Combat => Color::rgb(1.0, 0.5, 0.4),
+            MessageCategory::Examine => Color::rgb(0.6, 0.85, 1.0),
+            MessageCategory::Error => Color::rgb(1.0, 0.25, 0.25),
+        }
+    }
+}
+
+/// A message for the player, shown in the chatbox.
+pub struct GameMessage {
+    pub category: MessageCategory,
+    pub text: String,
+}
+
+impl GameMessage {
+    pub fn new(category: MessageCategory, text: impl Into<String>) -> Self {
+        Self {
+            category,
+            text: text.into(),
+        }
+    }
+}
+
+struct LoggedMessage {
+    category: MessageCategory,
+    text: String,
+    /// Seconds since startup when the message was sent.
+    time: f32,
+}
+
+#[derive(Resource)]
+pub struct ChatLog {
+    /// Oldest first.
+    messages: VecDeque<LoggedMessage>,
+    /// Categories currently shown.
+    pub shown: HashSet<MessageCategory>,
+    /// How many shown messages the view is scrolled up from the newest.
+    scroll: usize,
+}
+
+impl Default for ChatLog {
+    fn default() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            shown: MessageCategory::ALL.into_iter().collect(),
+            scroll: 0,
+        }
+    }
+}
+
+impl ChatLog {
+    fn shown_messages(&self) -> impl DoubleEndedIterator<Item = &LoggedMessage> + '_ {
+        self.messages
+            .iter()
+            .filter(|message| self.shown.contains(&message.category))
+    }
+}
+
+#[derive(Component)]
+struct Chatbox;
+
+#[derive(Component)]
+struct ChatboxText;
+
+#[derive(Component)]
+struct ChatFilterTab(MessageCategory);
+
+fn spawn_chatbox(mut commands: Commands, font: Res<UiFont>) {
+    let text_style = TextStyle {
+        font: font.0.clone(),
+        font_size: CHAT_FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.0),
+                        bottom: Val::Px(CHATBOX_BOTTOM),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(CHATBOX_SIZE.x), Val::Px(CHATBOX_SIZE.y)),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.36, 0.33, 0.27, 0.85).into(),
+                ..default()
+            },
+            Interaction::default(),
+            Chatbox,
+        ))
+        .with_children(|parent| {
+            parent.spawn(NodeBundle::default()).with_children(|tabs| {
+                for category in MessageCategory::ALL {
+                    tabs.spawn((
+                        ButtonBundle {
+                            style: Style {
+                                margin: UiRect::right(Val::Px(4.0)),
+                                padding: UiRect::all(Val::Px(3.0)),
+                                ..default()
+                            },
+                            background_color: TAB_COLOR.into(),
+                            ..default()
+                        },
+                        ChatFilterTab(category),
+                        Tooltip(format!("Show or hide {} messages", category.name())),
+                    ))
+                    .with_children(|tab| {
+                        tab.spawn(TextBundle::from_section(
+                            category.name(),
+                            text_style.clone(),
+                        ));
+                    });
+                }
+            });
+
+            parent.spawn((
+                TextBundle::from_section("", text_style.clone()).with_style(Style {
+                    margin: UiRect::top(Val::Px(4.0)),
+                    ..default()
+                }),
+                ChatboxText,
+            ));
+        });
+}
+
+fn record_game_messages(
+    time: Res<Time>,
+    mut events: EventReader<GameMessage>,
+    mut log: ResMut<ChatLog>,
+) {
+    for event in events.iter() {
+        log.messages.push_back(LoggedMessage {
+            category: event.category,
+            text: event.text.clone(),
+            time: time.elapsed_seconds(),
+        });
+        if log.messages.len() > MAX_MESSAGES {
+            log.messages.pop_front();
+        }
+        // Stay put while reading older messages
+        if log.scroll > 0 && log.shown.contains(&event.category) {
+            log.scroll += 1;
+        }
+    }
+}
+
+fn toggle_chat_filters(
+    mut log: ResMut<ChatLog>,
+    mut tabs: Query<(&Interaction, &ChatFilterTab, &mut BackgroundColor), Changed<Interaction>>,
+) {
+    for (interaction, tab, mut background) in tabs.iter_mut() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        if !log.shown.remove(&tab.0) {
+            log.shown.insert(tab.0);
+        }
+        log.scroll = 0;
+        *background = if log.shown.contains(&tab.0) {
+            TAB_COLOR.into()
+        } else {
+            TAB_OFF_COLOR.into()
+        };
+    }
+}
+
+fn scroll_chatbox(
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    chatbox: Query<&Interaction, With<Chatbox>>,
+    mut log: ResMut<ChatLog>,
+) {
+    let delta: f32 = mouse_wheel_events.iter().map(|event| event.y).sum();
+    if delta == 0.0
+        || chatbox
+            .iter()
+            .all(|interaction| *interaction == Interaction::None)
+    {
+        return;
+    }
+
+    let max_scroll = log.shown_messages().count().saturating_sub(VISIBLE_LINES);
+    let scroll = if delta > 0.0 {
+        (log.scroll + 1).min(max_scroll)
+    } else {
+        log.scroll.saturating_sub(1)
+    };
+    if log.scroll != scroll {
+        log.scroll = scroll;
+    }
+}
+
+fn update_chatbox_text(
+    log: Res<ChatLog>,
+    font: Res<UiFont>,
+    mut text: Query<&mut Text, With<ChatboxText>>,
+) {
+    if !log.is_changed() {
+        return;
+    }
+
+    let style = |color: Color| TextStyle {
+        font: font.0.clone(),
+        font_size: CHAT_FONT_SIZE,
+        color,
+    };
+
+    let mut lines: Vec<&LoggedMessage> = log
+        .shown_messages()
+        .rev()
+        .skip(log.scroll)
+        .take(VISIBLE_LINES)
+        .collect();
+    lines.reverse();
+
+    let mut sections = Vec::new();
+    for (index, message) in lines.into_iter().enumerate() {
+        if index > 0 {
+            sections.push(TextSection::new("\n", style(Color::WHITE)));
+        }
+        let seconds = message.time as u32;
+        sections.push(TextSection::new(
+            format!("[{:02}:{:02}] ", seconds / 60, seconds % 60),
+            style(Color::GRAY),
+        ));
+        sections.push(TextSection::new(
+            message.text.clone(),
+            style(message.category.color()),
+        ));
+    }
+
+    for mut text in text.iter_mut() {
+        text.sections = sections.clone();
+    }
+}
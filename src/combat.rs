@@ -0,0 +1,616 @@
+//! Combat: units ordered to attack walk into range and then trade hits on a
+//! fixed interval, using simplified OSRS accuracy and max hit formulas.
+//! Ranged attackers fire projectiles that deal their damage on arrival.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::building::BuildTask;
+use crate::camera::ShakeCamera;
+use crate::chatbox::{GameMessage, MessageCategory};
+use crate::health::Health;
+use crate::input::{Action, ActionInput};
+use crate::notable_events::{NotableEventKind, NotableEvents};
+use crate::npc::wander;
+use crate::rng::GameRng;
+use crate::tick::Interpolated;
+use crate::{Destination, Gameplay, Moving, Selected};
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageDealt>()
+            .add_event::<ToggleCombatOption>()
+            .init_resource::<CombatSettings>()
+            .add_startup_system(setup_projectile_assets)
+            .add_system(toggle_attack_style.in_set(Gameplay))
+            .add_system(apply_default_auto_retaliate.in_set(Gameplay))
+            .add_system(toggle_auto_retaliate.in_set(Gameplay))
+            .add_system(
+                apply_combat_toggles
+                    .in_base_set(CoreSet::PostUpdate)
+                    .in_set(Gameplay),
+            )
+            .add_system(
+                retaliate
+                    .after(apply_damage)
+                    .in_set(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            // Combat rolls and wandering both draw from `GameRng`, so they
+            // take turns in a fixed order for replays to come out the same
+            .add_systems(
+                (
+                    process_attack_tasks.after(wander),
+                    move_projectiles,
+                    apply_damage,
+                    handle_deaths,
+                )
+                    .chain()
+                    .in_set(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CombatSettings {
+    /// Auto-retaliate setting given to newly spawned player units.
+    pub auto_retaliate_by_default: bool,
+    /// How far a unit runs when hit with auto-retaliate off.
+    pub flee_distance: f32,
+}
+
+impl Default for CombatSettings {
+    fn default() -> Self {
+        Self {
+            auto_retaliate_by_default: true,
+            flee_distance: 4.0,
+        }
+    }
+}
+
+/// Whether an idle unit fights back when attacked. If not, it runs away.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AutoRetaliate(pub bool);
+
+/// Which side an entity fights for. Units never attack their own faction.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Hostile,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CombatStats {
+    pub attack: u32,
+    pub strength: u32,
+    pub defence: u32,
+    /// Distance from which the unit can hit its target.
+    pub attack_range: f32,
+    /// Seconds between attacks (4 OSRS ticks by default).
+    pub attack_interval: f32,
+}
+
+impl Default for CombatStats {
+    fn default() -> Self {
+        Self {
+            attack: 1,
+            strength: 1,
+            defence: 1,
+            attack_range: 0.8,
+            attack_interval: 2.4,
+        }
+    }
+}
+
+impl CombatStats {
+    /// Chance to land a hit on `defender`, following the OSRS accuracy roll
+    /// with no equipment bonuses.
+    pub fn hit_chance(&self, defender: &CombatStats) -> f32 {
+        let attack_roll = ((self.attack + 8) * 64) as f32;
+        let defence_roll = ((defender.defence + 8) * 64) as f32;
+
+        if attack_roll > defence_roll {
+            1.0 - (defence_roll + 2.0) / (2.0 * (attack_roll + 1.0))
+        } else {
+            attack_roll / (2.0 * (defence_roll + 1.0))
+        }
+    }
+
+    /// Highest damage a single hit can do, with no equipment bonuses.
+    pub fn max_hit(&self) -> u32 {
+        let effective_strength = (self.strength + 8) as f32;
+        (0.5 + effective_strength * 64.0 / 640.0) as u32
+    }
+}
+
+/// Stats of a bow or other ranged weapon.
+#[derive(Clone, Copy, Debug)]
+pub struct RangedWeapon {
+    /// Distance from which the weapon can hit.
+    pub range: f32,
+    /// Projectile speed in world units per second.
+    pub projectile_speed: f32,
+    /// Peak height of the projectile's arc above the straight line.
+    pub arc_height: f32,
+}
+
+impl RangedWeapon {
+    pub const SHORTBOW: RangedWeapon = RangedWeapon {
+        range: 4.0,
+        projectile_speed: 6.0,
+        arc_height: 0.6,
+    };
+}
+
+/// How a unit attacks. Units without this component fight in melee.
+#[derive(Component, Clone, Copy, Debug)]
+pub enum AttackStyle {
+    Melee,
+    Ranged(RangedWeapon),
+}
+
+/// Order to fight `target` until one of the two dies.
+#[derive(Component)]
+pub struct AttackTask {
+    pub target: Entity,
+    /// Seconds until the next attack can be made.
+    cooldown: f32,
+}
+
+impl AttackTask {
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            cooldown: 0.0,
+        }
+    }
+}
+
+/// Sent for every attack roll, including misses (`amount == 0`).
+pub struct DamageDealt {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub amount: u32,
+    /// Where the target stood when hit, for effects that outlive it.
+    pub position: Vec3,
+}
+
+/// A shot in flight. It follows its target and applies `amount` damage on
+/// arrival, or disappears if the target dies first.
+#[derive(Component)]
+pub struct Projectile {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub amount: u32,
+    start: Vec3,
+    speed: f32,
+    arc_height: f32,
+    /// Fraction of the flight completed, from 0 to 1.
+    progress: f32,
+}
+
+#[derive(Resource)]
+struct ProjectileAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+/// Height above a target's origin that projectiles aim for.
+const PROJECTILE_AIM_HEIGHT: f32 = 0.5;
+/// Share of a player unit's max health a single hit has to take to shake the
+/// camera.
+const BIG_HIT_SHARE: f32 = 0.25;
+
+/// Finds the closest attackable entity along `ray`, treating each candidate
+/// as a sphere resting on the ground.
+pub fn pick_attack_target(
+    ray: Ray,
+    candidates: impl Iterator<Item = (Entity, Vec3)>,
+) -> Option<Entity> {
+    const RADIUS: f32 = 0.5;
+
+    candidates
+        .filter_map(|(entity, position)| {
+            let center = position + Vec3::Y * RADIUS;
+            let along_ray = (center - ray.origin).dot(ray.direction);
+            if along_ray < 0.0 {
+                return None;
+            }
+            let closest_point = ray.origin + ray.direction * along_ray;
+            (closest_point.distance(center) <= RADIUS).then_some((entity, along_ray))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+fn setup_projectile_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(ProjectileAssets {
+        mesh: meshes.add(
+            shape::UVSphere {
+                radius: 0.05,
+                ..default()
+            }
+            .into(),
+        ),
+        material: materials.add(Color::rgb(0.45, 0.3, 0.15).into()),
+    });
+}
+
+/// The player switching some of their units' combat options.
+#[derive(Clone, Debug)]
+pub enum ToggleCombatOption {
+    /// Switches the units between melee and a shortbow.
+    AttackStyle(Vec<Entity>),
+    /// Turns the units' auto-retaliate on or off.
+    AutoRetaliate(Vec<Entity>),
+}
+
+fn toggle_attack_style(
+    actions: ActionInput,
+    selected: Query<Entity, (With<Selected>, With<CombatStats>)>,
+    mut toggles: EventWriter<ToggleCombatOption>,
+) {
+    if !actions.just_pressed(Action::ToggleAttackStyle) || selected.is_empty() {
+        return;
+    }
+
+    toggles.send(ToggleCombatOption::AttackStyle(selected.iter().collect()));
+}
+
+fn apply_default_auto_retaliate(
+    mut commands: Commands,
+    settings: Res<CombatSettings>,
+    units: Query<(Entity, &Faction), (With<CombatStats>, Without<AutoRetaliate>)>,
+) {
+    for (entity, faction) in units.iter() {
+        if *faction == Faction::Player {
+            commands
+                .entity(entity)
+                .insert(AutoRetaliate(settings.auto_retaliate_by_default));
+        }
+    }
+}
+
+fn toggle_auto_retaliate(
+    actions: ActionInput,
+    selected: Query<Entity, (With<Selected>, With<AutoRetaliate>)>,
+    mut toggles: EventWriter<ToggleCombatOption>,
+) {
+    if !actions.just_pressed(Action::ToggleAutoRetaliate) || selected.is_empty() {
+        return;
+    }
+
+    toggles.send(ToggleCombatOption::AutoRetaliate(selected.iter().collect()));
+}
+
+/// Switches the combat options the player toggled this frame. Units that
+/// have died since are skipped.
+pub fn apply_combat_toggles(
+    mut commands: Commands,
+    mut toggles: EventReader<ToggleCombatOption>,
+    mut units: Query<(Option<&AttackStyle>, Option<&mut AutoRetaliate>), With<CombatStats>>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    let (mut melee, mut ranged) = (false, false);
+    let (mut on, mut off) = (false, false);
+    for toggle in toggles.iter() {
+        match toggle {
+            ToggleCombatOption::AttackStyle(toggled) => {
+                for &unit in toggled {
+                    let Ok((style, _)) = units.get(unit) else {
+                        continue;
+                    };
+                    let style = match style {
+                        Some(AttackStyle::Ranged(_)) => AttackStyle::Melee,
+                        _ => AttackStyle::Ranged(RangedWeapon::SHORTBOW),
+                    };
+                    match style {
+                        AttackStyle::Melee => melee = true,
+                        AttackStyle::Ranged(_) => ranged = true,
+                    }
+                    commands.entity(unit).insert(style);
+                }
+            }
+            ToggleCombatOption::AutoRetaliate(toggled) => {
+                for &unit in toggled {
+                    let Ok((_, Some(mut auto_retaliate))) = units.get_mut(unit) else {
+                        continue;
+                    };
+                    auto_retaliate.0 = !auto_retaliate.0;
+                    if auto_retaliate.0 {
+                        on = true;
+                    } else {
+                        off = true;
+                    }
+                }
+            }
+        }
+    }
+
+    if melee {
+        messages.send(GameMessage::new(
+            MessageCategory::Combat,
+            "Your units switch to melee.",
+        ));
+    }
+    if ranged {
+        messages.send(GameMessage::new(
+            MessageCategory::Combat,
+            "Your units switch to shortbows.",
+        ));
+    }
+    if on {
+        messages.send(GameMessage::new(
+            MessageCategory::Combat,
+            "Auto-retaliate: On",
+        ));
+    }
+    if off {
+        messages.send(GameMessage::new(
+            MessageCategory::Combat,
+            "Auto-retaliate: Off",
+        ));
+    }
+}
+
+/// Makes idle units that were just hit either fight back or run away from
+/// their attacker, depending on their `AutoRetaliate` flag.
+fn retaliate(
+    mut commands: Commands,
+    settings: Res<CombatSettings>,
+    mut damage_events: EventReader<DamageDealt>,
+    defenders: Query<
+        (&Transform, &AutoRetaliate),
+        (Without<AttackTask>, Without<BuildTask>, Without<Moving>),
+    >,
+    attackers: Query<&Transform, With<Health>>,
+) {
+    for event in damage_events.iter() {
+        let Ok((transform, auto_retaliate)) = defenders.get(event.target) else {
+            continue;
+        };
+        let Ok(attacker_transform) = attackers.get(event.attacker) else {
+            continue;
+        };
+
+        if auto_retaliate.0 {
+            commands
+                .entity(event.target)
+                .insert(AttackTask::new(event.attacker));
+        } else {
+            let mut away = transform.translation - attacker_transform.translation;
+            away.y = 0.0;
+            let destination =
+                transform.translation + away.normalize_or_zero() * settings.flee_distance;
+            commands
+                .entity(event.target)
+                .insert((Moving {}, Destination(destination)));
+        }
+    }
+}
+
+fn process_attack_tasks(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    projectile_assets: Res<ProjectileAssets>,
+    mut units: ParamSet<(
+        Query<(
+            Entity,
+            &mut Transform,
+            &CombatStats,
+            &mut AttackTask,
+            Option<&AttackStyle>,
+            Option<&Destination>,
+        )>,
+        Query<(Entity, &Transform, Option<&CombatStats>), With<Health>>,
+    )>,
+    mut damage_events: EventWriter<DamageDealt>,
+    mut rng: ResMut<GameRng>,
+) {
+    // Attackers may be targets too, so look the targets up first
+    let targets: HashMap<Entity, (Vec3, Option<CombatStats>)> = units
+        .p1()
+        .iter()
+        .map(|(entity, transform, stats)| (entity, (transform.translation, stats.copied())))
+        .collect();
+
+    for (entity, mut transform, stats, mut task, style, destination) in units.p0().iter_mut() {
+        let Some(&(target_position, target_stats)) = targets.get(&task.target) else {
+            commands.entity(entity).remove::<AttackTask>();
+            continue;
+        };
+
+        task.cooldown -= fixed_time.period.as_secs_f32();
+
+        let mut offset = target_position - transform.translation;
+        offset.y = 0.0;
+
+        let range = match style {
+            Some(AttackStyle::Ranged(weapon)) => weapon.range,
+            _ => stats.attack_range,
+        };
+
+        // Walk towards the target until it is in range
+        if offset.length() > range {
+            commands
+                .entity(entity)
+                .insert((Moving {}, Destination(target_position)));
+            continue;
+        }
+
+        if destination.is_some() {
+            commands.entity(entity).remove::<(Moving, Destination)>();
+        }
+        transform.rotation = Quat::from_rotation_y(offset.x.atan2(offset.z));
+
+        if task.cooldown > 0.0 {
+            continue;
+        }
+        task.cooldown = stats.attack_interval;
+
+        let defender = target_stats.unwrap_or_default();
+        let amount = if rng.gen::<f32>() < stats.hit_chance(&defender) {
+            rng.gen_range(0..=stats.max_hit())
+        } else {
+            0
+        };
+
+        match style {
+            Some(AttackStyle::Ranged(weapon)) => {
+                let start = transform.translation + Vec3::Y * PROJECTILE_AIM_HEIGHT;
+                commands.spawn((
+                    PbrBundle {
+                        mesh: projectile_assets.mesh.clone(),
+                        material: projectile_assets.material.clone(),
+                        transform: Transform::from_translation(start),
+                        ..default()
+                    },
+                    Projectile {
+                        attacker: entity,
+                        target: task.target,
+                        amount,
+                        start,
+                        speed: weapon.projectile_speed,
+                        arc_height: weapon.arc_height,
+                        progress: 0.0,
+                    },
+                    Interpolated::default(),
+                ));
+            }
+            _ => damage_events.send(DamageDealt {
+                attacker: entity,
+                target: task.target,
+                amount,
+                position: target_position,
+            }),
+        }
+    }
+}
+
+fn move_projectiles(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Transform), Without<Health>>,
+    targets: Query<&Transform, With<Health>>,
+    mut damage_events: EventWriter<DamageDealt>,
+) {
+    for (entity, mut projectile, mut transform) in projectiles.iter_mut() {
+        let Ok(target_transform) = targets.get(projectile.target) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        // Home in on wherever the target is now
+        let end = target_transform.translation + Vec3::Y * PROJECTILE_AIM_HEIGHT;
+        let flight_distance = projectile.start.distance(end).max(0.01);
+        projectile.progress += projectile.speed * fixed_time.period.as_secs_f32() / flight_distance;
+
+        if projectile.progress >= 1.0 {
+            damage_events.send(DamageDealt {
+                attacker: projectile.attacker,
+                target: projectile.target,
+                amount: projectile.amount,
+                position: target_transform.translation,
+            });
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let progress = projectile.progress;
+        let arc = 4.0 * progress * (1.0 - progress) * projectile.arc_height;
+        transform.translation = projectile.start.lerp(end, progress) + Vec3::Y * arc;
+    }
+}
+
+fn apply_damage(
+    mut damage_events: EventReader<DamageDealt>,
+    mut health: Query<&mut Health>,
+    targets: Query<(&Faction, &GlobalTransform)>,
+    mut notable_events: ResMut<NotableEvents>,
+    mut shakes: EventWriter<ShakeCamera>,
+) {
+    for event in damage_events.iter() {
+        debug!(
+            "{:?} hit {:?} for {}",
+            event.attacker, event.target, event.amount
+        );
+        let Ok(mut health) = health.get_mut(event.target) else {
+            continue;
+        };
+        let dealt = health.damage(event.amount);
+
+        if let Ok((Faction::Player, transform)) = targets.get(event.target) {
+            notable_events.push(NotableEventKind::UnitAttacked, transform.translation());
+
+            // Shake for hits that take a big chunk of a player unit's health
+            let share = dealt as f32 / health.max as f32;
+            if share >= BIG_HIT_SHARE {
+                shakes.send(ShakeCamera(share));
+            }
+        }
+    }
+}
+
+fn handle_deaths(mut commands: Commands, health: Query<(Entity, &Health), Changed<Health>>) {
+    for (entity, health) in health.iter() {
+        if health.is_dead() {
+            info!("{:?} died", entity);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(attack: u32, strength: u32, defence: u32) -> CombatStats {
+        CombatStats {
+            attack,
+            strength,
+            defence,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn even_fights_hit_just_under_half_the_time() {
+        for level in [1, 50, 99] {
+            let chance = stats(level, 1, level).hit_chance(&stats(1, 1, level));
+            assert!(chance < 0.5 && chance > 0.49, "{}", chance);
+        }
+    }
+
+    #[test]
+    fn one_level_more_attack_hits_over_half_the_time() {
+        let chance = stats(2, 1, 1).hit_chance(&stats(1, 1, 1));
+        assert!(chance > 0.5 && chance < 0.6, "{}", chance);
+    }
+
+    #[test]
+    fn hit_chance_never_reaches_certainty() {
+        let weakest = stats(0, 0, 0);
+        let strongest = stats(99, 99, 99);
+
+        let best = strongest.hit_chance(&weakest);
+        assert!(best < 1.0 && best > 0.95, "{}", best);
+        let worst = weakest.hit_chance(&strongest);
+        assert!(worst > 0.0 && worst < 0.05, "{}", worst);
+    }
+
+    #[test]
+    fn max_hit_at_lowest_and_highest_strength() {
+        assert_eq!(stats(1, 1, 1).max_hit(), 1);
+        assert_eq!(stats(1, 99, 1).max_hit(), 11);
+    }
+}
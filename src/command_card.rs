@@ -0,0 +1,203 @@
+//! The command card: a panel at the bottom centre of the screen with a
+//! button for each order the current selection can be given. Buttons fire
+//! the same `Action`s as the hotkeys, so anything added here behaves exactly
+//! like pressing its key.
+
+use bevy::prelude::*;
+use bevy::ui::UiSystem;
+
+use crate::combat::{AutoRetaliate, CombatStats};
+use crate::input::{Action, InputBindings, TriggeredActions};
+use crate::tooltip::Tooltip;
+use crate::ui::UiFont;
+use crate::{Selected, UnitType};
+
+pub struct CommandCardPlugin;
+
+impl Plugin for CommandCardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_command_card)
+            .add_system(
+                press_command_buttons
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(UiSystem::Focus),
+            )
+            .add_system(update_command_card)
+            .add_system(update_command_labels);
+    }
+}
+
+const BUTTON_SIZE: Vec2 = Vec2::new(90.0, 40.0);
+const BUTTON_MARGIN: f32 = 4.0;
+const BUTTON_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+const BUTTON_HOVERED: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// Every button the card can show, in order, with its label and tooltip.
+const COMMANDS: [(Action, &str, &str); 4] = [
+    (
+        Action::Stop,
+        "Stop",
+        "Stop what the selected units are doing and drop their queued orders.",
+    ),
+    (
+        Action::BuildMenu,
+        "Build",
+        "Choose a structure for the selected workers to build.",
+    ),
+    (
+        Action::ToggleAttackStyle,
+        "Attack style",
+        "Switch the selected units between melee and shortbows.",
+    ),
+    (
+        Action::ToggleAutoRetaliate,
+        "Retaliate",
+        "Toggle whether the selected units fight back when attacked.",
+    ),
+];
+
+#[derive(Component)]
+struct CommandCard;
+
+#[derive(Component)]
+struct CommandButton(Action);
+
+/// The text showing which key a button's action is bound to.
+#[derive(Component)]
+struct CommandKeyLabel(Action);
+
+fn spawn_command_card(mut commands: Commands, font: Res<UiFont>, bindings: Res<InputBindings>) {
+    let width = COMMANDS.len() as f32 * (BUTTON_SIZE.x + 2.0 * BUTTON_MARGIN);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Percent(50.0),
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
+                    margin: UiRect::left(Val::Px(-width / 2.0)),
+                    size: Size::width(Val::Px(width)),
+                    justify_content: JustifyContent::Center,
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            },
+            Interaction::default(),
+            CommandCard,
+        ))
+        .with_children(|parent| {
+            for (action, label, tooltip) in COMMANDS {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(BUTTON_SIZE.x), Val::Px(BUTTON_SIZE.y)),
+                                margin: UiRect::all(Val::Px(BUTTON_MARGIN)),
+                                flex_direction: FlexDirection::Column,
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                        CommandButton(action),
+                        Tooltip(tooltip.to_string()),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font: font.0.clone(),
+                                font_size: 14.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                        button.spawn((
+                            TextBundle::from_section(
+                                bindings.key_name(action).unwrap_or_default(),
+                                TextStyle {
+                                    font: font.0.clone(),
+                                    font_size: 11.0,
+                                    color: Color::rgb(1.0, 0.85, 0.3),
+                                },
+                            ),
+                            CommandKeyLabel(action),
+                        ));
+                    });
+            }
+        });
+}
+
+fn press_command_buttons(
+    mut buttons: Query<(&Interaction, &CommandButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut triggered: ResMut<TriggeredActions>,
+) {
+    for (interaction, button, mut background) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {
+                triggered.0.insert(button.0);
+            }
+            Interaction::Hovered => *background = BUTTON_HOVERED.into(),
+            Interaction::None => *background = BUTTON_COLOR.into(),
+        }
+    }
+}
+
+/// Shows the card while units are selected, with only the buttons that
+/// apply to at least one of them.
+fn update_command_card(
+    selected: Query<(&UnitType, Option<&CombatStats>, Option<&AutoRetaliate>), With<Selected>>,
+    mut card: Query<&mut Style, (With<CommandCard>, Without<CommandButton>)>,
+    mut buttons: Query<(&CommandButton, &mut Style), Without<CommandCard>>,
+) {
+    let available = |action: Action| match action {
+        Action::BuildMenu => selected
+            .iter()
+            .any(|(unit_type, ..)| *unit_type == UnitType::Worker),
+        Action::ToggleAttackStyle => selected.iter().any(|(_, stats, _)| stats.is_some()),
+        Action::ToggleAutoRetaliate => selected.iter().any(|(.., retaliate)| retaliate.is_some()),
+        _ => !selected.is_empty(),
+    };
+
+    for mut style in card.iter_mut() {
+        let display = if selected.is_empty() {
+            Display::None
+        } else {
+            Display::Flex
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    for (button, mut style) in buttons.iter_mut() {
+        let display = if available(button.0) {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+}
+
+fn update_command_labels(
+    bindings: Res<InputBindings>,
+    mut labels: Query<(&CommandKeyLabel, &mut Text)>,
+) {
+    if !bindings.is_changed() {
+        return;
+    }
+
+    for (label, mut text) in labels.iter_mut() {
+        text.sections[0].value = bindings.key_name(label.0).unwrap_or_default();
+    }
+}
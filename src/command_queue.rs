@@ -0,0 +1,167 @@
+//! Shift-queued orders. Orders given while Shift is held are appended to a
+//! unit's `CommandQueue` instead of replacing what it is doing, and are
+//! started one at a time whenever the unit runs out of work. S stops the
+//! selected units, dropping their current and queued orders.
+//!
+//! Orders from the player arrive as `PlayerOrder` events and are carried
+//! out at the end of the frame, which lets replays record and replay them.
+
+use std::collections::VecDeque;
+
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+
+use crate::building::BuildTask;
+use crate::combat::AttackTask;
+use crate::input::{Action, ActionInput};
+use crate::{Destination, Gameplay, Movable, Moving, Selected};
+
+pub struct CommandQueuePlugin;
+
+impl Plugin for CommandQueuePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerOrder>()
+            .add_system(stop_selected_units.in_set(Gameplay))
+            .add_system(
+                carry_out_orders
+                    .in_base_set(CoreSet::PostUpdate)
+                    .in_set(Gameplay),
+            )
+            .add_system(
+                start_queued_commands
+                    .in_set(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+/// A single order a unit can be given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnitCommand {
+    Move(Vec3),
+    Attack(Entity),
+    Build(Entity),
+}
+
+impl UnitCommand {
+    /// Replaces whatever the unit is doing with this order.
+    pub fn start(self, unit: &mut EntityCommands) {
+        match self {
+            UnitCommand::Move(point) => {
+                unit.insert((Moving {}, Destination(point)))
+                    .remove::<(AttackTask, BuildTask)>();
+            }
+            UnitCommand::Attack(target) => {
+                unit.insert(AttackTask::new(target)).remove::<BuildTask>();
+            }
+            UnitCommand::Build(site) => {
+                unit.insert(BuildTask { site }).remove::<AttackTask>();
+            }
+        }
+    }
+}
+
+/// An order from the player to some of their units.
+#[derive(Clone, Debug)]
+pub enum PlayerOrder {
+    /// Gives `command`, after the units' current orders when `queued`.
+    Command {
+        units: Vec<Entity>,
+        command: UnitCommand,
+        queued: bool,
+    },
+    /// Drops the units' current and queued orders.
+    Stop(Vec<Entity>),
+}
+
+/// Orders waiting for the unit to finish its current one, oldest first.
+#[derive(Component, Default)]
+pub struct CommandQueue(pub VecDeque<UnitCommand>);
+
+/// Gives a unit an order, either replacing its current orders or, when
+/// `queued`, after the ones it already has.
+fn give_command(
+    commands: &mut Commands,
+    unit: Entity,
+    queue: Option<Mut<CommandQueue>>,
+    command: UnitCommand,
+    queued: bool,
+) {
+    match (queued, queue) {
+        (true, Some(mut queue)) => queue.0.push_back(command),
+        (true, None) => {
+            commands
+                .entity(unit)
+                .insert(CommandQueue(VecDeque::from([command])));
+        }
+        (false, queue) => {
+            if let Some(mut queue) = queue {
+                queue.0.clear();
+            }
+            command.start(&mut commands.entity(unit));
+        }
+    }
+}
+
+fn start_queued_commands(
+    mut commands: Commands,
+    mut units: Query<
+        (Entity, &mut CommandQueue),
+        (Without<Moving>, Without<AttackTask>, Without<BuildTask>),
+    >,
+) {
+    for (entity, mut queue) in units.iter_mut() {
+        if let Some(command) = queue.0.pop_front() {
+            command.start(&mut commands.entity(entity));
+        }
+    }
+}
+
+/// Carries out the orders the player gave this frame. Orders for units
+/// that have died since are dropped.
+pub fn carry_out_orders(
+    mut commands: Commands,
+    mut orders: EventReader<PlayerOrder>,
+    mut units: Query<Option<&mut CommandQueue>, With<Movable>>,
+) {
+    for order in orders.iter() {
+        match order {
+            PlayerOrder::Command {
+                units: ordered,
+                command,
+                queued,
+            } => {
+                for &unit in ordered {
+                    if let Ok(queue) = units.get_mut(unit) {
+                        give_command(&mut commands, unit, queue, *command, *queued);
+                    }
+                }
+            }
+            PlayerOrder::Stop(stopped) => {
+                for &unit in stopped {
+                    let Ok(queue) = units.get_mut(unit) else {
+                        continue;
+                    };
+                    if let Some(mut queue) = queue {
+                        queue.0.clear();
+                    }
+                    commands
+                        .entity(unit)
+                        .remove::<(Moving, Destination, AttackTask, BuildTask)>();
+                }
+            }
+        }
+    }
+}
+
+fn stop_selected_units(
+    actions: ActionInput,
+    selected: Query<Entity, With<Selected>>,
+    mut orders: EventWriter<PlayerOrder>,
+) {
+    if !actions.just_pressed(Action::Stop) || selected.is_empty() {
+        return;
+    }
+
+    orders.send(PlayerOrder::Stop(selected.iter().collect()));
+}
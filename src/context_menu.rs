@@ -0,0 +1,313 @@
+//! OSRS-style right-click menu. While enabled, right-clicking in the world
+//! opens a menu listing everything that can be done with what is under the
+//! cursor ("Attack Goblin", "Walk here", "Examine House") instead of giving
+//! the default order straight away. M switches between the menu and
+//! RTS-style instant orders, which stay the default.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::building::ConstructionSite;
+use crate::camera::MainCamera;
+use crate::chatbox::{GameMessage, MessageCategory};
+use crate::combat::{pick_attack_target, Faction};
+use crate::command_queue::{PlayerOrder, UnitCommand};
+use crate::grid::Footprint;
+use crate::health::Health;
+use crate::input::{cursor_ray, ground_point, Action, ActionInput};
+use crate::terrain::TileMap;
+use crate::ui::{PointerOverUi, Popup, UiFont};
+use crate::{Gameplay, Selected, UnitType};
+
+pub struct ContextMenuPlugin;
+
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContextMenuSettings>()
+            .add_system(toggle_context_menu.in_set(Gameplay))
+            .add_system(
+                open_context_menu
+                    .after(toggle_context_menu)
+                    .in_set(Gameplay),
+            )
+            .add_system(choose_context_menu_option.in_set(Gameplay))
+            .add_system(
+                close_context_menu
+                    .after(choose_context_menu_option)
+                    .in_set(Gameplay),
+            );
+    }
+}
+
+const MENU_FONT_SIZE: f32 = 14.0;
+const MENU_BACKGROUND: Color = Color::rgb(0.36, 0.33, 0.27);
+const TITLE_COLOR: Color = Color::rgb(0.8, 0.75, 0.62);
+const OPTION_HOVERED: Color = Color::rgb(0.47, 0.43, 0.35);
+/// Colour of unit names in the menu.
+const UNIT_COLOR: Color = Color::rgb(1.0, 1.0, 0.0);
+/// Colour of structure names in the menu.
+const OBJECT_COLOR: Color = Color::rgb(0.0, 1.0, 1.0);
+
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextMenuSettings {
+    /// Open a menu on right-click instead of giving the default order.
+    pub enabled: bool,
+}
+
+#[derive(Clone, Copy)]
+enum MenuAction {
+    Command(UnitCommand),
+    Examine(&'static str),
+    Cancel,
+}
+
+struct MenuEntry {
+    verb: &'static str,
+    /// Name of the thing the option acts on, and the colour it is shown in.
+    target: Option<(&'static str, Color)>,
+    action: MenuAction,
+}
+
+#[derive(Component)]
+struct ContextMenu;
+
+#[derive(Component)]
+struct ContextMenuOption(MenuAction);
+
+fn toggle_context_menu(
+    actions: ActionInput,
+    mut settings: ResMut<ContextMenuSettings>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    if actions.just_pressed(Action::ToggleContextMenu) {
+        settings.enabled = !settings.enabled;
+        let text = if settings.enabled {
+            "Right-click now opens a menu."
+        } else {
+            "Right-click now gives orders."
+        };
+        messages.send(GameMessage::new(MessageCategory::Game, text));
+    }
+}
+
+fn open_context_menu(
+    mut commands: Commands,
+    mouse_button_input: Res<Input<MouseButton>>,
+    settings: Res<ContextMenuSettings>,
+    pointer_over_ui: Res<PointerOverUi>,
+    font: Res<UiFont>,
+    windows: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    units: Query<(Entity, &GlobalTransform, &Faction, &UnitType), With<Health>>,
+    construction_sites: Query<(Entity, &Footprint, &ConstructionSite)>,
+    selected: Query<(), With<Selected>>,
+    menus: Query<Entity, With<ContextMenu>>,
+    tile_map: Res<TileMap>,
+) {
+    if !settings.enabled
+        || !mouse_button_input.just_pressed(MouseButton::Right)
+        || pointer_over_ui.0
+    {
+        return;
+    }
+
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    let window = windows.single();
+    let Some(cursor_position) = window.cursor_position() else {
+        return;
+    };
+    let (camera, camera_transform) = camera.single();
+    let Some(ray) = cursor_ray(window, camera, camera_transform) else {
+        return;
+    };
+    let Some(point) = ground_point(ray, &tile_map) else {
+        return;
+    };
+
+    let unit = pick_attack_target(
+        ray,
+        units
+            .iter()
+            .map(|(entity, transform, ..)| (entity, transform.translation())),
+    )
+    .and_then(|entity| units.get(entity).ok());
+    let site = construction_sites
+        .iter()
+        .find(|(_, footprint, _)| footprint.contains(point));
+    let has_selection = !selected.is_empty();
+
+    // The first option is the one an instant right-click would pick
+    let mut entries = Vec::new();
+    if has_selection {
+        match (unit, site) {
+            (Some((target, _, Faction::Hostile, unit_type)), _) => entries.push(MenuEntry {
+                verb: "Attack",
+                target: Some((unit_type.name(), UNIT_COLOR)),
+                action: MenuAction::Command(UnitCommand::Attack(target)),
+            }),
+            (_, Some((site, _, construction))) => entries.push(MenuEntry {
+                verb: "Help build",
+                target: Some((construction.kind.name(), OBJECT_COLOR)),
+                action: MenuAction::Command(UnitCommand::Build(site)),
+            }),
+            _ => {}
+        }
+        // Walking onto water goes to the shore
+        if let Some(point) = tile_map.nearest_walkable(point) {
+            entries.push(MenuEntry {
+                verb: "Walk here",
+                target: None,
+                action: MenuAction::Command(UnitCommand::Move(point)),
+            });
+        }
+    }
+    if let Some((_, _, _, unit_type)) = unit {
+        entries.push(MenuEntry {
+            verb: "Examine",
+            target: Some((unit_type.name(), UNIT_COLOR)),
+            action: MenuAction::Examine(unit_type.examine()),
+        });
+    }
+    if let Some((_, _, construction)) = site {
+        entries.push(MenuEntry {
+            verb: "Examine",
+            target: Some((construction.kind.name(), OBJECT_COLOR)),
+            action: MenuAction::Examine(construction.kind.examine()),
+        });
+    }
+    if entries.is_empty() {
+        return;
+    }
+    entries.push(MenuEntry {
+        verb: "Cancel",
+        target: None,
+        action: MenuAction::Cancel,
+    });
+
+    let text_style = |color: Color| TextStyle {
+        font: font.0.clone(),
+        font_size: MENU_FONT_SIZE,
+        color,
+    };
+
+    // Cursor positions start at the bottom-left corner, UI at the top-left
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(cursor_position.x),
+                        top: Val::Px(window.height() - cursor_position.y),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                background_color: MENU_BACKGROUND.into(),
+                ..default()
+            },
+            Interaction::default(),
+            ContextMenu,
+            Popup,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Choose Option",
+                text_style(TITLE_COLOR),
+            ));
+            for entry in entries {
+                let mut sections = vec![TextSection::new(entry.verb, text_style(Color::WHITE))];
+                if let Some((name, color)) = entry.target {
+                    sections.push(TextSection::new(format!(" {}", name), text_style(color)));
+                }
+
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::new(
+                                    Val::Px(4.0),
+                                    Val::Px(4.0),
+                                    Val::Px(1.0),
+                                    Val::Px(1.0),
+                                ),
+                                ..default()
+                            },
+                            background_color: Color::NONE.into(),
+                            ..default()
+                        },
+                        ContextMenuOption(entry.action),
+                    ))
+                    .with_children(|option| {
+                        option.spawn(TextBundle::from_sections(sections));
+                    });
+            }
+        });
+}
+
+fn choose_context_menu_option(
+    mut commands: Commands,
+    actions: ActionInput,
+    mut options: Query<
+        (&Interaction, &ContextMenuOption, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    selected: Query<Entity, With<Selected>>,
+    menus: Query<Entity, With<ContextMenu>>,
+    mut messages: EventWriter<GameMessage>,
+    mut orders: EventWriter<PlayerOrder>,
+) {
+    let mut chosen = None;
+    for (interaction, option, mut background) in options.iter_mut() {
+        match interaction {
+            Interaction::Clicked => chosen = Some(option.0),
+            Interaction::Hovered => *background = OPTION_HOVERED.into(),
+            Interaction::None => *background = Color::NONE.into(),
+        }
+    }
+    let Some(action) = chosen else {
+        return;
+    };
+
+    match action {
+        MenuAction::Command(command) => {
+            orders.send(PlayerOrder::Command {
+                units: selected.iter().collect(),
+                command,
+                queued: actions.pressed(Action::AddModifier),
+            });
+        }
+        MenuAction::Examine(text) => {
+            messages.send(GameMessage::new(MessageCategory::Examine, text));
+        }
+        MenuAction::Cancel => {}
+    }
+
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+/// Closes the menu when clicking anywhere outside it or pressing Escape.
+fn close_context_menu(
+    mut commands: Commands,
+    actions: ActionInput,
+    mouse_button_input: Res<Input<MouseButton>>,
+    pointer_over_ui: Res<PointerOverUi>,
+    menus: Query<Entity, With<ContextMenu>>,
+) {
+    let clicked_outside = mouse_button_input.just_pressed(MouseButton::Left) && !pointer_over_ui.0;
+    if !clicked_outside && !actions.just_pressed(Action::Cancel) {
+        return;
+    }
+
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
@@ -0,0 +1,105 @@
+//! RTS control groups: Ctrl+1–9 stores the current selection in a group,
+//! 1–9 selects the group again and pressing the same number twice quickly
+//! also centres the camera on it.
+
+use bevy::prelude::*;
+
+use crate::camera::CameraJump;
+use crate::input::{Action, ActionInput};
+use crate::{Gameplay, Movable, Selected, SelectionChanged};
+
+pub struct ControlGroupsPlugin;
+
+impl Plugin for ControlGroupsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlGroups>()
+            .add_system(remove_dead_group_members.in_set(Gameplay))
+            .add_system(
+                control_group_hotkeys
+                    .after(remove_dead_group_members)
+                    .in_set(Gameplay),
+            );
+    }
+}
+
+pub const GROUP_COUNT: usize = 9;
+
+/// Seconds within which a second press of the same group key counts as a
+/// double tap.
+const DOUBLE_TAP_WINDOW: f32 = 0.4;
+
+#[derive(Resource, Default)]
+pub struct ControlGroups {
+    /// Members of each group, indexed by the group's key minus one.
+    pub groups: [Vec<Entity>; GROUP_COUNT],
+    /// The last group selected and when, for detecting double taps.
+    last_recall: Option<(usize, f32)>,
+}
+
+fn remove_dead_group_members(
+    mut control_groups: ResMut<ControlGroups>,
+    mut removed: RemovedComponents<Movable>,
+) {
+    let removed: Vec<Entity> = removed.iter().collect();
+    if removed.is_empty() {
+        return;
+    }
+
+    for group in control_groups.groups.iter_mut() {
+        group.retain(|entity| !removed.contains(entity));
+    }
+}
+
+fn control_group_hotkeys(
+    mut commands: Commands,
+    actions: ActionInput,
+    time: Res<Time>,
+    mut control_groups: ResMut<ControlGroups>,
+    selected: Query<Entity, With<Selected>>,
+    units: Query<&GlobalTransform, With<Movable>>,
+    mut jumps: EventWriter<CameraJump>,
+    mut selection_changed: EventWriter<SelectionChanged>,
+) {
+    let Some(index) =
+        (0..GROUP_COUNT).find(|group| actions.just_pressed(Action::ControlGroup(*group)))
+    else {
+        return;
+    };
+
+    if actions.pressed(Action::ControlModifier) {
+        control_groups.groups[index] = selected.iter().collect();
+        control_groups.last_recall = None;
+        return;
+    }
+
+    let members = &control_groups.groups[index];
+    if members.is_empty() {
+        return;
+    }
+
+    for entity in selected.iter() {
+        commands.entity(entity).remove::<Selected>();
+    }
+    for entity in members.iter() {
+        commands.entity(*entity).insert(Selected {});
+    }
+    selection_changed.send(SelectionChanged);
+
+    let now = time.elapsed_seconds();
+    let double_tap = matches!(
+        control_groups.last_recall,
+        Some((last, at)) if last == index && now - at <= DOUBLE_TAP_WINDOW
+    );
+    if double_tap {
+        let positions: Vec<Vec3> = members
+            .iter()
+            .filter_map(|entity| units.get(*entity).ok())
+            .map(|transform| transform.translation())
+            .collect();
+        if !positions.is_empty() {
+            let center = positions.iter().sum::<Vec3>() / positions.len() as f32;
+            jumps.send(CameraJump(center));
+        }
+    }
+    control_groups.last_recall = Some((index, now));
+}
@@ -0,0 +1,241 @@
+//! Doodads: props scattered over the map so it looks lived in. Bushes and
+//! flowers grow on the grass, pebbles lie on grass and hills, and fences
+//! line the roads. Nothing can be done with them, but bushes and fences take
+//! up their tile, so units walk around them and nothing is built on top.
+
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::building::spawn_map_buildings;
+use crate::grid::{OccupancyGrid, TILE_SIZE};
+use crate::map_file::MapData;
+use crate::rng::GameRng;
+use crate::terrain::{TileKind, TileMap};
+use crate::water::spawn_fishing_spots;
+
+pub struct DoodadPlugin;
+
+impl Plugin for DoodadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(
+            // After the map's structures have taken their tiles, and after
+            // the fishing spots so the same seed always gives the same world
+            scatter_doodads
+                .in_base_set(StartupSet::PostStartup)
+                .after(spawn_map_buildings)
+                .after(spawn_fishing_spots),
+        )
+        .add_system(clear_repainted_doodads);
+    }
+}
+
+/// Distance around the player's starting positions kept free of doodads.
+const START_CLEARANCE: f32 = 2.0;
+/// Chance of a fence along each side of a tile that faces a road.
+const FENCE_CHANCE: f64 = 0.6;
+const FLOWER_COLORS: [Color; 3] = [
+    Color::rgb(0.85, 0.2, 0.25),
+    Color::rgb(0.95, 0.85, 0.3),
+    Color::rgb(0.7, 0.55, 0.9),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoodadKind {
+    Bush,
+    Flowers,
+    Pebbles,
+    Fence,
+}
+
+impl DoodadKind {
+    /// Whether the doodad takes up its tile, so units can't walk through it
+    /// and structures can't be built there.
+    pub fn blocks(&self) -> bool {
+        match self {
+            DoodadKind::Bush | DoodadKind::Fence => true,
+            DoodadKind::Flowers | DoodadKind::Pebbles => false,
+        }
+    }
+
+    /// Chance of the doodad on a tile of the given kind. Fences follow the
+    /// roads instead.
+    fn chance(&self, tile: TileKind) -> f64 {
+        match (self, tile) {
+            (DoodadKind::Bush, TileKind::Grass) => 0.04,
+            (DoodadKind::Bush, TileKind::Forest) => 0.2,
+            (DoodadKind::Flowers, TileKind::Grass) => 0.06,
+            (DoodadKind::Pebbles, TileKind::Grass) => 0.03,
+            (DoodadKind::Pebbles, TileKind::Hills) => 0.2,
+            _ => 0.0,
+        }
+    }
+}
+
+/// A prop standing on a tile of the map.
+#[derive(Component)]
+pub struct Doodad {
+    pub kind: DoodadKind,
+    pub tile: IVec2,
+    /// Kind of the tile when the doodad was put there.
+    ground: TileKind,
+}
+
+pub fn scatter_doodads(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    tile_map: Res<TileMap>,
+    map: Res<MapData>,
+    mut occupancy: ResMut<OccupancyGrid>,
+    mut rng: ResMut<GameRng>,
+) {
+    let sphere = |radius: f32| shape::UVSphere {
+        radius,
+        ..default()
+    };
+    let bush_mesh = meshes.add(sphere(0.3).into());
+    let flower_mesh = meshes.add(sphere(0.06).into());
+    let pebble_mesh = meshes.add(sphere(0.1).into());
+    let fence_mesh = meshes.add(shape::Box::new(TILE_SIZE, 0.35, 0.05).into());
+    let bush_material = materials.add(Color::rgb(0.2, 0.42, 0.18).into());
+    let flower_materials: Vec<_> = FLOWER_COLORS
+        .iter()
+        .map(|color| materials.add((*color).into()))
+        .collect();
+    let pebble_material = materials.add(Color::rgb(0.55, 0.55, 0.52).into());
+    let fence_material = materials.add(Color::rgb(0.45, 0.32, 0.2).into());
+
+    for (tile, ground) in tile_map.tiles() {
+        let center = (tile.as_vec2() + Vec2::splat(0.5)) * TILE_SIZE;
+        let near_start = map
+            .player_starts
+            .iter()
+            .any(|start| Vec2::from(*start).distance(center) < START_CLEARANCE);
+        if occupancy.is_occupied(tile) || near_start || !tile_map.is_walkable(tile) {
+            continue;
+        }
+
+        let mut spawn = |kind: DoodadKind,
+                         offset: Vec2,
+                         transform: Transform,
+                         mesh: Handle<Mesh>,
+                         material: Handle<StandardMaterial>| {
+            let position =
+                tile_map.ground_position(Vec3::new(center.x + offset.x, 0.0, center.y + offset.y));
+            commands.spawn((
+                PbrBundle {
+                    mesh,
+                    material,
+                    transform: transform.with_translation(position + transform.translation),
+                    ..default()
+                },
+                Name::new(format!("{:?}", kind)),
+                Doodad { kind, tile, ground },
+            ));
+            if kind.blocks() {
+                occupancy.occupy([tile]);
+            }
+        };
+
+        // Fences on the sides facing a road, rather than anything else
+        if matches!(ground, TileKind::Grass | TileKind::Forest) {
+            let roads: Vec<IVec2> = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                .into_iter()
+                .filter(|side| tile_map.get(tile + *side) == Some(TileKind::Road))
+                .collect();
+            for side in roads.iter() {
+                if !rng.gen_bool(FENCE_CHANCE) {
+                    continue;
+                }
+                // Just inside the edge, running along it
+                let offset = side.as_vec2() * TILE_SIZE * 0.45;
+                let rotation = if side.x == 0 { 0.0 } else { FRAC_PI_2 };
+                spawn(
+                    DoodadKind::Fence,
+                    offset,
+                    Transform::from_xyz(0.0, 0.175, 0.0)
+                        .with_rotation(Quat::from_rotation_y(rotation)),
+                    fence_mesh.clone(),
+                    fence_material.clone(),
+                );
+            }
+            if !roads.is_empty() {
+                continue;
+            }
+        }
+
+        let Some(kind) = [DoodadKind::Bush, DoodadKind::Flowers, DoodadKind::Pebbles]
+            .into_iter()
+            .find(|kind| rng.gen_bool(kind.chance(ground)))
+        else {
+            continue;
+        };
+        let scatter =
+            |rng: &mut GameRng| Vec2::new(rng.gen_range(-0.3..0.3), rng.gen_range(-0.3..0.3));
+        match kind {
+            DoodadKind::Bush => {
+                let size = rng.gen_range(0.8..1.2);
+                spawn(
+                    kind,
+                    scatter(&mut rng) * 0.5,
+                    Transform::from_xyz(0.0, 0.2 * size, 0.0).with_scale(Vec3::new(
+                        size,
+                        0.75 * size,
+                        size,
+                    )),
+                    bush_mesh.clone(),
+                    bush_material.clone(),
+                );
+            }
+            DoodadKind::Flowers => {
+                for _ in 0..3 {
+                    let material = flower_materials.choose(&mut *rng).unwrap().clone();
+                    spawn(
+                        kind,
+                        scatter(&mut rng),
+                        Transform::from_xyz(0.0, 0.06, 0.0),
+                        flower_mesh.clone(),
+                        material,
+                    );
+                }
+            }
+            DoodadKind::Pebbles => {
+                for _ in 0..2 {
+                    spawn(
+                        kind,
+                        scatter(&mut rng),
+                        Transform::from_xyz(0.0, 0.03, 0.0).with_scale(Vec3::new(1.0, 0.5, 1.0)),
+                        pebble_mesh.clone(),
+                        pebble_material.clone(),
+                    );
+                }
+            }
+            DoodadKind::Fence => {}
+        }
+    }
+}
+
+/// Removes doodads from tiles whose kind was changed in the map editor,
+/// freeing the tiles they took.
+fn clear_repainted_doodads(
+    mut commands: Commands,
+    tile_map: Res<TileMap>,
+    mut occupancy: ResMut<OccupancyGrid>,
+    doodads: Query<(Entity, &Doodad)>,
+) {
+    if !tile_map.is_changed() || tile_map.is_added() {
+        return;
+    }
+
+    for (entity, doodad) in doodads.iter() {
+        if tile_map.get(doodad.tile) != Some(doodad.ground) {
+            commands.entity(entity).despawn_recursive();
+            if doodad.kind.blocks() {
+                occupancy.free([doodad.tile]);
+            }
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! The map editor. F11 switches between playing and editing the map. While
+//! editing, gameplay stops and a toolbar on the left picks what left
+//! clicking on the map does: paint a kind of tile, put up a structure, add a
+//! player start position, or erase structures and start positions. Save
+//! writes the map to the file it was loaded from, or to a new map file. The
+//! camera, minimap and selection work as they do in the game.
+
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use crate::building::{spawn_building, BuildAssets, BuildMode, Building, BuildingKind};
+use crate::camera::MainCamera;
+use crate::chatbox::{GameMessage, MessageCategory};
+use crate::grid::{tile_at, Footprint, OccupancyGrid};
+use crate::input::{cursor_ray, ground_point, Action, ActionInput};
+use crate::map_file::{map_path_from_args, MapBuilding, MapData};
+use crate::terrain::{TileKind, TileMap};
+use crate::tooltip::Tooltip;
+use crate::ui::{PointerOverUi, UiFont};
+use crate::{GameState, UNIT_CLEARANCE};
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditorTool>()
+            .add_startup_system(setup_editor_assets)
+            .add_system(toggle_editor)
+            .add_system(enter_editor.in_schedule(OnEnter(GameState::Editor)))
+            .add_system(leave_editor.in_schedule(OnExit(GameState::Editor)))
+            .add_system(press_editor_buttons.run_if(in_state(GameState::Editor)))
+            .add_system(
+                use_editor_tool
+                    .after(press_editor_buttons)
+                    .run_if(in_state(GameState::Editor)),
+            )
+            .add_system(
+                update_start_markers
+                    .after(use_editor_tool)
+                    .run_if(in_state(GameState::Editor)),
+            );
+    }
+}
+
+/// Where Save writes a map that wasn't loaded from a file.
+const NEW_MAP_PATH: &str = "assets/maps/new_map.ron";
+/// How close to a start position a click has to be to erase it.
+const START_MARKER_RADIUS: f32 = 0.5;
+const BUTTON_COLOR: Color = Color::rgb(0.25, 0.22, 0.18);
+const BUTTON_HOVERED: Color = Color::rgb(0.36, 0.32, 0.26);
+const BUTTON_ACTIVE: Color = Color::rgb(0.5, 0.42, 0.25);
+
+/// What left clicking on the map does in the editor.
+#[derive(Resource, Clone, Copy, PartialEq, Eq)]
+enum EditorTool {
+    Paint(TileKind),
+    Structure(BuildingKind),
+    PlayerStart,
+    Erase,
+}
+
+impl Default for EditorTool {
+    fn default() -> Self {
+        EditorTool::Paint(TileKind::Grass)
+    }
+}
+
+impl EditorTool {
+    const ALL: [EditorTool; 10] = [
+        EditorTool::Paint(TileKind::Grass),
+        EditorTool::Paint(TileKind::Forest),
+        EditorTool::Paint(TileKind::Hills),
+        EditorTool::Paint(TileKind::Water),
+        EditorTool::Paint(TileKind::Road),
+        EditorTool::Structure(BuildingKind::House),
+        EditorTool::Structure(BuildingKind::Watchtower),
+        EditorTool::Structure(BuildingKind::Wall),
+        EditorTool::PlayerStart,
+        EditorTool::Erase,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            EditorTool::Paint(kind) => kind.name(),
+            EditorTool::Structure(kind) => kind.name(),
+            EditorTool::PlayerStart => "Start position",
+            EditorTool::Erase => "Erase",
+        }
+    }
+
+    fn tooltip(&self) -> String {
+        match self {
+            EditorTool::Paint(kind) => {
+                format!("Hold the left button to paint {} tiles.", kind.name())
+            }
+            EditorTool::Structure(kind) => format!("Click to put up a {}.", kind.name()),
+            EditorTool::PlayerStart => "Click to add a place where a worker starts.".to_string(),
+            EditorTool::Erase => "Click a structure or start position to remove it.".to_string(),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct EditorAssets {
+    start_marker_mesh: Handle<Mesh>,
+    start_marker_material: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct EditorToolbar;
+
+#[derive(Component, Clone, Copy)]
+enum EditorButton {
+    Tool(EditorTool),
+    Save,
+}
+
+/// Shows a player start position while editing.
+#[derive(Component)]
+struct StartMarker;
+
+fn setup_editor_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(EditorAssets {
+        start_marker_mesh: meshes.add(
+            shape::Capsule {
+                radius: 0.1,
+                depth: 0.6,
+                ..default()
+            }
+            .into(),
+        ),
+        start_marker_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.3, 0.6, 1.0),
+            emissive: Color::rgb(0.1, 0.2, 0.4),
+            ..default()
+        }),
+    });
+}
+
+fn toggle_editor(
+    actions: ActionInput,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !actions.just_pressed(Action::ToggleEditor) {
+        return;
+    }
+
+    match state.0 {
+        GameState::InGame => next_state.set(GameState::Editor),
+        GameState::Editor => next_state.set(GameState::InGame),
+        GameState::Paused => {}
+    }
+}
+
+fn enter_editor(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    mut build_mode: ResMut<BuildMode>,
+    mut map: ResMut<MapData>,
+) {
+    build_mode.placing = None;
+    // Shows the start markers
+    map.set_changed();
+
+    let text_style = TextStyle {
+        font: font.0.clone(),
+        font_size: 14.0,
+        color: Color::WHITE,
+    };
+    let button = |parent: &mut ChildBuilder, label: &str, tooltip: String, action| {
+        parent
+            .spawn((
+                ButtonBundle {
+                    style: Style {
+                        margin: UiRect::top(Val::Px(4.0)),
+                        padding: UiRect::all(Val::Px(5.0)),
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                },
+                action,
+                Tooltip(tooltip),
+            ))
+            .with_children(|button| {
+                button.spawn(TextBundle::from_section(label, text_style.clone()));
+            });
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.0),
+                        top: Val::Px(10.0),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.36, 0.33, 0.27, 0.9).into(),
+                ..default()
+            },
+            Interaction::default(),
+            EditorToolbar,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Map editor (F11 to play)",
+                text_style.clone(),
+            ));
+            for tool in EditorTool::ALL {
+                button(
+                    parent,
+                    tool.name(),
+                    tool.tooltip(),
+                    EditorButton::Tool(tool),
+                );
+            }
+            button(
+                parent,
+                "Save",
+                "Write the map to its map file.".to_string(),
+                EditorButton::Save,
+            );
+        });
+}
+
+fn leave_editor(
+    mut commands: Commands,
+    editor_entities: Query<Entity, Or<(With<EditorToolbar>, With<StartMarker>)>>,
+) {
+    for entity in editor_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn press_editor_buttons(
+    mut tool: ResMut<EditorTool>,
+    mut buttons: Query<(Ref<Interaction>, &EditorButton, &mut BackgroundColor)>,
+    map: Res<MapData>,
+    tile_map: Res<TileMap>,
+    buildings: Query<(&Building, &Footprint)>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    for (interaction, button, _) in buttons.iter() {
+        if !interaction.is_changed() || *interaction != Interaction::Clicked {
+            continue;
+        }
+        match *button {
+            EditorButton::Tool(new_tool) => {
+                if *tool != new_tool {
+                    *tool = new_tool;
+                }
+            }
+            EditorButton::Save => {
+                let path = map_path_from_args().unwrap_or_else(|| PathBuf::from(NEW_MAP_PATH));
+                let buildings = buildings
+                    .iter()
+                    .map(|(building, footprint)| MapBuilding {
+                        kind: building.kind,
+                        tile: footprint.min.into(),
+                    })
+                    .collect();
+                let data = MapData::from_tile_map(&tile_map, buildings, map.player_starts.clone());
+                let message = match data.save(&path) {
+                    Ok(()) => GameMessage::new(
+                        MessageCategory::Game,
+                        format!("Saved the map to {}.", path.display()),
+                    ),
+                    Err(error) => GameMessage::new(
+                        MessageCategory::Error,
+                        format!("Couldn't save the map: {}", error),
+                    ),
+                };
+                messages.send(message);
+            }
+        }
+    }
+
+    // The current tool's button stays highlighted
+    for (interaction, button, mut background) in buttons.iter_mut() {
+        let color = match (*interaction, button) {
+            (_, EditorButton::Tool(button_tool)) if *button_tool == *tool => BUTTON_ACTIVE,
+            (Interaction::None, _) => BUTTON_COLOR,
+            _ => BUTTON_HOVERED,
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+fn use_editor_tool(
+    mut commands: Commands,
+    mouse_button_input: Res<Input<MouseButton>>,
+    pointer_over_ui: Res<PointerOverUi>,
+    tool: Res<EditorTool>,
+    assets: Res<BuildAssets>,
+    windows: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut tile_map: ResMut<TileMap>,
+    mut map: ResMut<MapData>,
+    mut occupancy: ResMut<OccupancyGrid>,
+    buildings: Query<(Entity, &Footprint), With<Building>>,
+    mut messages: EventWriter<GameMessage>,
+) {
+    // Tiles paint while the button is held, everything else once per click
+    let clicked = match *tool {
+        EditorTool::Paint(_) => mouse_button_input.pressed(MouseButton::Left),
+        _ => mouse_button_input.just_pressed(MouseButton::Left),
+    };
+    if !clicked || pointer_over_ui.0 {
+        return;
+    }
+
+    let (camera, camera_transform) = camera.single();
+    let Some(point) = cursor_ray(windows.single(), camera, camera_transform)
+        .and_then(|ray| ground_point(ray, &tile_map))
+    else {
+        return;
+    };
+
+    match *tool {
+        EditorTool::Paint(kind) => {
+            // Every change rebuilds the ground mesh, so leave matching tiles
+            // alone
+            let tile = tile_at(point);
+            if tile_map.get(tile).is_some_and(|current| current != kind) {
+                tile_map.set(tile, kind);
+            }
+        }
+        EditorTool::Structure(kind) => {
+            let footprint = Footprint::centered_at(point, kind.footprint_size());
+            if footprint
+                .tiles()
+                .all(|tile| tile_map.is_walkable(tile) && !occupancy.is_occupied(tile))
+            {
+                spawn_building(
+                    &mut commands,
+                    &assets,
+                    &tile_map,
+                    &mut occupancy,
+                    kind,
+                    footprint,
+                );
+            } else {
+                messages.send(GameMessage::new(
+                    MessageCategory::Error,
+                    "You can't build there.",
+                ));
+            }
+        }
+        EditorTool::PlayerStart => {
+            if tile_map.is_walkable(tile_at(point)) {
+                map.player_starts.push((point.x, point.z));
+            } else {
+                messages.send(GameMessage::new(
+                    MessageCategory::Error,
+                    "Workers can't start there.",
+                ));
+            }
+        }
+        EditorTool::Erase => {
+            let ground = Vec2::new(point.x, point.z);
+            if let Some((entity, footprint)) = buildings
+                .iter()
+                .find(|(_, footprint)| footprint.contains(point))
+            {
+                occupancy.free(footprint.tiles());
+                commands.entity(entity).despawn_recursive();
+            } else if let Some(index) = map
+                .player_starts
+                .iter()
+                .position(|(x, z)| Vec2::new(*x, *z).distance(ground) < START_MARKER_RADIUS)
+            {
+                map.player_starts.remove(index);
+            }
+        }
+    }
+}
+
+/// Puts a marker on every start position whenever they or the ground
+/// change.
+fn update_start_markers(
+    mut commands: Commands,
+    map: Res<MapData>,
+    tile_map: Res<TileMap>,
+    assets: Res<EditorAssets>,
+    markers: Query<Entity, With<StartMarker>>,
+) {
+    if !map.is_changed() && !tile_map.is_changed() {
+        return;
+    }
+
+    for marker in markers.iter() {
+        commands.entity(marker).despawn_recursive();
+    }
+    for (x, z) in map.player_starts.iter() {
+        let position = tile_map.ground_position(Vec3::new(*x, 0.0, *z));
+        commands.spawn((
+            PbrBundle {
+                mesh: assets.start_marker_mesh.clone(),
+                material: assets.start_marker_material.clone(),
+                transform: Transform::from_translation(position + Vec3::Y * (0.4 + UNIT_CLEARANCE)),
+                ..default()
+            },
+            StartMarker,
+        ));
+    }
+}
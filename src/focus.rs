@@ -0,0 +1,106 @@
+//! The focused unit: the one selected unit that panels show details for.
+//! Tab cycles the focus through the selection, and a marker floats above the
+//! focused unit while more than one unit is selected.
+
+use bevy::prelude::*;
+
+use crate::input::{Action, ActionInput};
+use crate::ui::{UiFont, WorldAnchor};
+use crate::{Gameplay, Selected};
+
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FocusedUnit>()
+            .add_system(keep_focus_in_selection.in_set(Gameplay))
+            .add_system(cycle_focus.after(keep_focus_in_selection).in_set(Gameplay))
+            .add_system(update_focus_marker.after(cycle_focus));
+    }
+}
+
+const FOCUS_MARKER_OFFSET: Vec3 = Vec3::new(0.0, 1.5, 0.0);
+
+/// The selected unit whose details are shown, if any unit is selected.
+#[derive(Resource, Default)]
+pub struct FocusedUnit(pub Option<Entity>);
+
+#[derive(Component)]
+struct FocusMarker;
+
+/// Moves the focus to another selected unit when the focused one is
+/// deselected or dies, and clears it when nothing is selected.
+fn keep_focus_in_selection(
+    mut focused: ResMut<FocusedUnit>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    if focused.0.is_some_and(|entity| selected.contains(entity)) {
+        return;
+    }
+
+    let first = selected.iter().min();
+    if focused.0 != first {
+        focused.0 = first;
+    }
+}
+
+fn cycle_focus(
+    actions: ActionInput,
+    mut focused: ResMut<FocusedUnit>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    if !actions.just_pressed(Action::CycleFocus) {
+        return;
+    }
+
+    let mut units: Vec<Entity> = selected.iter().collect();
+    if units.is_empty() {
+        return;
+    }
+    units.sort();
+    let next = units
+        .iter()
+        .find(|entity| focused.0.map_or(true, |current| **entity > current))
+        .unwrap_or(&units[0]);
+    focused.0 = Some(*next);
+}
+
+fn update_focus_marker(
+    mut commands: Commands,
+    focused: Res<FocusedUnit>,
+    selected: Query<(), With<Selected>>,
+    font: Res<UiFont>,
+    mut markers: Query<(Entity, &mut WorldAnchor), With<FocusMarker>>,
+) {
+    // Only worth pointing out when there is more than one to choose from
+    let target = focused.0.filter(|_| selected.iter().count() > 1);
+
+    match (target, markers.get_single_mut()) {
+        (Some(target), Ok((_, mut anchor))) => {
+            if anchor.target != target {
+                anchor.target = target;
+            }
+        }
+        (Some(target), Err(_)) => {
+            commands.spawn((
+                TextBundle::from_section(
+                    "▼",
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: 16.0,
+                        color: Color::rgb(1.0, 0.85, 0.3),
+                    },
+                ),
+                WorldAnchor {
+                    target,
+                    offset: FOCUS_MARKER_OFFSET,
+                },
+                FocusMarker,
+            ));
+        }
+        (None, Ok((marker, _))) => {
+            commands.entity(marker).despawn_recursive();
+        }
+        (None, Err(_)) => {}
+    }
+}
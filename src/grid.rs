@@ -0,0 +1,115 @@
+//! The tile grid the map is laid out on, and which tiles are blocked.
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+/// Size of one tile in world units.
+pub const TILE_SIZE: f32 = 1.0;
+
+/// Tiles that are taken by structures and cannot be built on, tiles units
+/// can't walk onto, and tiles reserved by units when only one unit may stand
+/// on each tile.
+#[derive(Resource, Default)]
+pub struct OccupancyGrid {
+    occupied: HashSet<IVec2>,
+    impassable: HashSet<IVec2>,
+    reserved: HashMap<IVec2, Entity>,
+}
+
+impl OccupancyGrid {
+    /// Whether the ground lets units walk onto a tile.
+    pub fn is_passable(&self, tile: IVec2) -> bool {
+        !self.impassable.contains(&tile)
+    }
+
+    /// Replaces the tiles units can't walk onto, such as water.
+    pub fn set_impassable(&mut self, tiles: impl IntoIterator<Item = IVec2>) {
+        self.impassable = tiles.into_iter().collect();
+    }
+
+    pub fn is_occupied(&self, tile: IVec2) -> bool {
+        self.occupied.contains(&tile)
+    }
+
+    pub fn occupy(&mut self, tiles: impl IntoIterator<Item = IVec2>) {
+        self.occupied.extend(tiles);
+    }
+
+    pub fn free(&mut self, tiles: impl IntoIterator<Item = IVec2>) {
+        for tile in tiles {
+            self.occupied.remove(&tile);
+        }
+    }
+
+    /// Reserves a tile for a unit. Returns false if another unit holds it.
+    pub fn reserve(&mut self, tile: IVec2, unit: Entity) -> bool {
+        *self.reserved.entry(tile).or_insert(unit) == unit
+    }
+
+    /// Frees every tile a unit holds except those in `keep`.
+    pub fn release(&mut self, unit: Entity, keep: &[IVec2]) {
+        self.reserved
+            .retain(|tile, holder| *holder != unit || keep.contains(tile));
+    }
+
+    pub fn clear_reservations(&mut self) {
+        self.reserved.clear();
+    }
+}
+
+/// Tile a world position lies on.
+pub fn tile_at(position: Vec3) -> IVec2 {
+    (Vec2::new(position.x, position.z) / TILE_SIZE)
+        .floor()
+        .as_ivec2()
+}
+
+/// A rectangle of tiles covered by something placed on the grid.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Footprint {
+    /// Tile with the lowest x and z coordinates.
+    pub min: IVec2,
+    pub size: UVec2,
+}
+
+impl Footprint {
+    /// Footprint of the given size as close as possible to being centred on
+    /// `position`.
+    pub fn centered_at(position: Vec3, size: UVec2) -> Self {
+        let half_size = size.as_vec2() * TILE_SIZE / 2.0;
+        let min = Vec2::new(position.x, position.z) - half_size;
+        Self {
+            min: (min / TILE_SIZE).round().as_ivec2(),
+            size,
+        }
+    }
+
+    /// World position of the footprint's centre at ground level.
+    pub fn center(&self) -> Vec3 {
+        let center = (self.min.as_vec2() + self.size.as_vec2() / 2.0) * TILE_SIZE;
+        Vec3::new(center.x, 0.0, center.y)
+    }
+
+    pub fn contains(&self, position: Vec3) -> bool {
+        let (min, max) = self.world_bounds();
+        (min.x..=max.x).contains(&position.x) && (min.y..=max.y).contains(&position.z)
+    }
+
+    /// Point on the footprint's edge (or inside it) closest to `position`,
+    /// at ground level.
+    pub fn closest_point(&self, position: Vec3) -> Vec3 {
+        let (min, max) = self.world_bounds();
+        let closest = Vec2::new(position.x, position.z).clamp(min, max);
+        Vec3::new(closest.x, 0.0, closest.y)
+    }
+
+    fn world_bounds(&self) -> (Vec2, Vec2) {
+        let min = self.min.as_vec2() * TILE_SIZE;
+        (min, min + self.size.as_vec2() * TILE_SIZE)
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = IVec2> + '_ {
+        (0..self.size.y as i32)
+            .flat_map(move |z| (0..self.size.x as i32).map(move |x| self.min + IVec2::new(x, z)))
+    }
+}
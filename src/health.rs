@@ -0,0 +1,122 @@
+//! Hitpoints for units and NPCs, with overhead health bars that show up
+//! when the owner is damaged or selected.
+
+use bevy::prelude::*;
+
+use crate::ui::WorldAnchor;
+use crate::Selected;
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_health_bars)
+            .add_system(update_health_bars);
+    }
+}
+
+/// How far above the owner's origin the health bar is drawn.
+const HEALTH_BAR_OFFSET: Vec3 = Vec3::new(0.0, 1.2, 0.0);
+const HEALTH_BAR_SIZE: Vec2 = Vec2::new(40.0, 5.0);
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max == 0 {
+            return 0.0;
+        }
+        self.current as f32 / self.max as f32
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.current < self.max
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+
+    /// Removes up to `amount` hitpoints and returns how many were removed.
+    pub fn damage(&mut self, amount: u32) -> u32 {
+        let dealt = amount.min(self.current);
+        self.current -= dealt;
+        dealt
+    }
+}
+
+#[derive(Component)]
+struct HealthBar {
+    owner: Entity,
+}
+
+#[derive(Component)]
+struct HealthBarFill;
+
+fn spawn_health_bars(mut commands: Commands, new_health: Query<Entity, Added<Health>>) {
+    for owner in new_health.iter() {
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(Val::Px(HEALTH_BAR_SIZE.x), Val::Px(HEALTH_BAR_SIZE.y)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.8, 0.1, 0.1).into(),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                HealthBar { owner },
+                WorldAnchor {
+                    target: owner,
+                    offset: HEALTH_BAR_OFFSET,
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.1, 0.8, 0.1).into(),
+                        ..default()
+                    },
+                    HealthBarFill,
+                ));
+            });
+    }
+}
+
+fn update_health_bars(
+    mut bars: Query<(&HealthBar, &mut Visibility, &Children)>,
+    owners: Query<(&Health, Option<&Selected>)>,
+    mut fills: Query<&mut Style, With<HealthBarFill>>,
+) {
+    for (bar, mut visibility, children) in bars.iter_mut() {
+        let Ok((health, selected)) = owners.get(bar.owner) else {
+            continue;
+        };
+
+        *visibility = if health.is_damaged() || selected.is_some() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+
+        for child in children.iter() {
+            if let Ok(mut style) = fills.get_mut(*child) {
+                style.size.width = Val::Percent(health.fraction() * 100.0);
+            }
+        }
+    }
+}
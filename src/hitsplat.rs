@@ -0,0 +1,99 @@
+//! OSRS-style hitsplats: a red splat with the damage number over whoever was
+//! hit, or a blue one when the attack did no damage. Splats fade out after
+//! about a second, staying where the target fell if the hit killed it.
+
+use bevy::prelude::*;
+
+use crate::combat::DamageDealt;
+use crate::ui::{OutlivesTarget, UiFont, WorldAnchor};
+
+pub struct HitsplatPlugin;
+
+impl Plugin for HitsplatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_hitsplats).add_system(fade_hitsplats);
+    }
+}
+
+const HITSPLAT_OFFSET: Vec3 = Vec3::new(0.0, 0.7, 0.0);
+const HITSPLAT_LIFETIME: f32 = 1.0;
+const DAMAGE_COLOR: Color = Color::rgb(0.75, 0.05, 0.05);
+const BLOCKED_COLOR: Color = Color::rgb(0.15, 0.35, 0.8);
+
+#[derive(Component)]
+struct Hitsplat {
+    timer: Timer,
+}
+
+fn spawn_hitsplats(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageDealt>,
+    font: Res<UiFont>,
+) {
+    for event in damage_events.iter() {
+        let color = if event.amount == 0 {
+            BLOCKED_COLOR
+        } else {
+            DAMAGE_COLOR
+        };
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(Val::Px(24.0), Val::Px(24.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+                Hitsplat {
+                    timer: Timer::from_seconds(HITSPLAT_LIFETIME, TimerMode::Once),
+                },
+                WorldAnchor {
+                    target: event.target,
+                    offset: HITSPLAT_OFFSET,
+                },
+                OutlivesTarget {
+                    last_position: event.position,
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    event.amount.to_string(),
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            });
+    }
+}
+
+fn fade_hitsplats(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut hitsplats: Query<(Entity, &mut Hitsplat, &mut BackgroundColor, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (entity, mut hitsplat, mut background, children) in hitsplats.iter_mut() {
+        hitsplat.timer.tick(time.delta());
+        if hitsplat.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // Stay fully visible for the first half, then fade out
+        let alpha = (hitsplat.timer.percent_left() * 2.0).min(1.0);
+        background.0.set_a(alpha);
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].style.color.set_a(alpha);
+            }
+        }
+    }
+}
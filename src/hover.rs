@@ -0,0 +1,131 @@
+//! Hover highlighting: the unit or structure under the cursor is kept in
+//! `Hovered` for other systems to read, and is drawn slightly brighter so
+//! it's clear what a click will hit.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::building::{Building, ConstructionSite};
+use crate::camera::MainCamera;
+use crate::combat::pick_attack_target;
+use crate::grid::Footprint;
+use crate::health::Health;
+use crate::input::{cursor_ray, ground_point};
+use crate::terrain::TileMap;
+use crate::ui::PointerOverUi;
+use crate::Gameplay;
+
+pub struct HoverPlugin;
+
+impl Plugin for HoverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hovered>()
+            .init_resource::<HoverMaterials>()
+            .add_system(update_hovered.in_set(Gameplay))
+            .add_system(tint_hovered.after(update_hovered).in_set(Gameplay));
+    }
+}
+
+/// How much brighter the hovered entity is drawn.
+const HOVER_BRIGHTNESS: f32 = 1.25;
+const HOVER_EMISSIVE: Color = Color::rgb(0.08, 0.08, 0.06);
+
+/// The unit or structure under the cursor, if any.
+#[derive(Resource, Default)]
+pub struct Hovered(pub Option<Entity>);
+
+/// Tinted copies of materials, keyed by the original, so hovering the same
+/// thing again doesn't create new ones.
+#[derive(Resource, Default)]
+struct HoverMaterials(HashMap<Handle<StandardMaterial>, Handle<StandardMaterial>>);
+
+/// A mesh of the hovered entity that is showing a tinted material.
+#[derive(Component)]
+struct HoverTint {
+    original: Handle<StandardMaterial>,
+}
+
+fn update_hovered(
+    mut hovered: ResMut<Hovered>,
+    pointer_over_ui: Res<PointerOverUi>,
+    windows: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    tile_map: Res<TileMap>,
+    units: Query<(Entity, &GlobalTransform), With<Health>>,
+    structures: Query<(Entity, &Footprint), Or<(With<ConstructionSite>, With<Building>)>>,
+) {
+    let (camera, camera_transform) = camera.single();
+    let ray = cursor_ray(windows.single(), camera, camera_transform).filter(|_| !pointer_over_ui.0);
+
+    let entity = ray.and_then(|ray| {
+        let unit = pick_attack_target(
+            ray,
+            units
+                .iter()
+                .map(|(entity, transform)| (entity, transform.translation())),
+        );
+        unit.or_else(|| {
+            let point = ground_point(ray, &tile_map)?;
+            structures
+                .iter()
+                .find(|(_, footprint)| footprint.contains(point))
+                .map(|(entity, _)| entity)
+        })
+    });
+
+    if hovered.0 != entity {
+        hovered.0 = entity;
+    }
+}
+
+/// Swaps the materials of the hovered entity's meshes for brighter copies,
+/// and puts the originals back once it is no longer hovered.
+fn tint_hovered(
+    mut commands: Commands,
+    hovered: Res<Hovered>,
+    mut hover_materials: ResMut<HoverMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    tinted: Query<(Entity, &HoverTint)>,
+    children: Query<&Children>,
+    mut mesh_materials: Query<&mut Handle<StandardMaterial>>,
+) {
+    if !hovered.is_changed() {
+        return;
+    }
+
+    for (entity, tint) in tinted.iter() {
+        // Leave materials that were swapped for something else meanwhile,
+        // like a construction site that got finished
+        if let Ok(mut material) = mesh_materials.get_mut(entity) {
+            if hover_materials.0.get(&tint.original) == Some(&*material) {
+                *material = tint.original.clone();
+            }
+        }
+        commands.entity(entity).remove::<HoverTint>();
+    }
+
+    let Some(target) = hovered.0 else {
+        return;
+    };
+
+    let meshes = std::iter::once(target).chain(children.iter_descendants(target));
+    for entity in meshes {
+        let Ok(mut material) = mesh_materials.get_mut(entity) else {
+            continue;
+        };
+        let original = material.clone();
+        let tinted = hover_materials
+            .0
+            .entry(original.clone())
+            .or_insert_with(|| {
+                let mut tinted = materials.get(&original).cloned().unwrap_or_default();
+                tinted.base_color *= HOVER_BRIGHTNESS;
+                tinted.emissive += HOVER_EMISSIVE;
+                materials.add(tinted)
+            })
+            .clone();
+
+        *material = tinted;
+        commands.entity(entity).insert(HoverTint { original });
+    }
+}
@@ -0,0 +1,165 @@
+//! Idle worker tracking: workers with nothing to do are flagged with
+//! `IdleWorker`, a badge in the bottom-left corner counts them, and the
+//! period key (or clicking the badge) selects the next one and moves the
+//! camera to it.
+
+use bevy::prelude::*;
+
+use crate::building::BuildTask;
+use crate::camera::CameraJump;
+use crate::combat::{AttackTask, Faction};
+use crate::input::{Action, ActionInput};
+use crate::tooltip::Tooltip;
+use crate::ui::UiFont;
+use crate::{Gameplay, Moving, Selected, SelectionChanged, UnitType};
+
+pub struct IdleWorkersPlugin;
+
+impl Plugin for IdleWorkersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_idle_worker_badge)
+            .add_system(flag_idle_workers.in_set(Gameplay))
+            .add_system(update_idle_worker_badge.after(flag_idle_workers))
+            .add_system(
+                select_next_idle_worker
+                    .after(flag_idle_workers)
+                    .in_set(Gameplay),
+            );
+    }
+}
+
+/// A player's worker that is not moving or working on anything.
+#[derive(Component)]
+pub struct IdleWorker;
+
+#[derive(Component)]
+struct IdleWorkerBadge;
+
+fn spawn_idle_worker_badge(mut commands: Commands, font: Res<UiFont>) {
+    commands
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.0),
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
+                    padding: UiRect::all(Val::Px(6.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            },
+            IdleWorkerBadge,
+            Tooltip("Select the next idle worker".to_string()),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 16.0,
+                    color: Color::rgb(1.0, 0.85, 0.3),
+                },
+            ));
+        });
+}
+
+fn flag_idle_workers(
+    mut commands: Commands,
+    workers: Query<(
+        Entity,
+        &UnitType,
+        &Faction,
+        Option<&IdleWorker>,
+        Option<&Moving>,
+        Option<&AttackTask>,
+        Option<&BuildTask>,
+    )>,
+) {
+    for (entity, unit_type, faction, idle, moving, attack_task, build_task) in workers.iter() {
+        if *unit_type != UnitType::Worker || *faction != Faction::Player {
+            continue;
+        }
+
+        let is_idle = moving.is_none() && attack_task.is_none() && build_task.is_none();
+        match (is_idle, idle.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(IdleWorker);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<IdleWorker>();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_idle_worker_badge(
+    idle_workers: Query<(), With<IdleWorker>>,
+    mut badge: Query<(&mut Style, &Children), With<IdleWorkerBadge>>,
+    mut texts: Query<&mut Text>,
+) {
+    let count = idle_workers.iter().count();
+
+    for (mut style, children) in badge.iter_mut() {
+        let display = if count == 0 {
+            Display::None
+        } else {
+            Display::Flex
+        };
+        if style.display != display {
+            style.display = display;
+        }
+
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                let value = format!("Idle workers: {}", count);
+                if text.sections[0].value != value {
+                    text.sections[0].value = value;
+                }
+            }
+        }
+    }
+}
+
+/// Selects the idle worker after the last one picked, so repeated presses
+/// cycle through all of them.
+fn select_next_idle_worker(
+    mut commands: Commands,
+    actions: ActionInput,
+    badge: Query<&Interaction, (Changed<Interaction>, With<IdleWorkerBadge>)>,
+    idle_workers: Query<(Entity, &GlobalTransform), With<IdleWorker>>,
+    selected: Query<Entity, With<Selected>>,
+    mut last_picked: Local<Option<Entity>>,
+    mut jumps: EventWriter<CameraJump>,
+    mut selection_changed: EventWriter<SelectionChanged>,
+) {
+    let badge_clicked = badge
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !actions.just_pressed(Action::NextIdleWorker) && !badge_clicked {
+        return;
+    }
+
+    let mut workers: Vec<(Entity, &GlobalTransform)> = idle_workers.iter().collect();
+    if workers.is_empty() {
+        return;
+    }
+    workers.sort_by_key(|(entity, _)| *entity);
+    let next = workers
+        .iter()
+        .find(|(entity, _)| last_picked.map_or(true, |last| *entity > last))
+        .unwrap_or(&workers[0]);
+
+    for entity in selected.iter() {
+        commands.entity(entity).remove::<Selected>();
+    }
+    commands.entity(next.0).insert(Selected {});
+    selection_changed.send(SelectionChanged);
+    jumps.send(CameraJump(next.1.translation()));
+    *last_picked = Some(next.0);
+}
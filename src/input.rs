@@ -0,0 +1,218 @@
+//! Named input actions and the keys bound to them. Input systems ask
+//! `ActionInput` about actions instead of checking key codes, so every
+//! hotkey can be remapped through `InputBindings`, and UI buttons can fire
+//! the same actions through `TriggeredActions`. Also has helpers for
+//! turning the cursor position into a point in the world.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::TileMap;
+
+pub struct InputBindingsPlugin;
+
+impl Plugin for InputBindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputBindings>()
+            .init_resource::<TriggeredActions>()
+            .add_system(clear_triggered_actions.in_base_set(CoreSet::First));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    CameraLeft,
+    CameraRight,
+    CameraUp,
+    CameraDown,
+    RotateCameraLeft,
+    RotateCameraRight,
+    CenterOnSelection,
+    /// Jump to, or with `ControlModifier` save, one of the camera bookmarks.
+    CameraBookmark(usize),
+    JumpToEvent,
+    ToggleMinimapNorthUp,
+    /// Select, or with `ControlModifier` assign, one of the control groups.
+    ControlGroup(usize),
+    NextIdleWorker,
+    Stop,
+    CycleFocus,
+    ToggleAttackStyle,
+    ToggleAutoRetaliate,
+    BuildMenu,
+    Cancel,
+    Pause,
+    ToggleToonShading,
+    ToggleFrameBudget,
+    ToggleContextMenu,
+    ToggleStatusPlates,
+    ToggleEditor,
+    QuickSave,
+    QuickLoad,
+    /// Held to add to the selection or keep placing buildings.
+    AddModifier,
+    /// Held to toggle units in the selection, assign control groups or save
+    /// camera bookmarks.
+    ControlModifier,
+}
+
+/// Keys bound to each action. An action can have several keys, and fires
+/// when any of them is pressed.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct InputBindings(pub HashMap<Action, Vec<KeyCode>>);
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let mut bindings = HashMap::from([
+            (Action::CameraLeft, vec![KeyCode::Left]),
+            (Action::CameraRight, vec![KeyCode::Right]),
+            (Action::CameraUp, vec![KeyCode::Up]),
+            (Action::CameraDown, vec![KeyCode::Down]),
+            (Action::RotateCameraLeft, vec![KeyCode::Q]),
+            (Action::RotateCameraRight, vec![KeyCode::E]),
+            (Action::CenterOnSelection, vec![KeyCode::Home]),
+            (Action::JumpToEvent, vec![KeyCode::Space]),
+            (Action::ToggleMinimapNorthUp, vec![KeyCode::N]),
+            (Action::NextIdleWorker, vec![KeyCode::Period]),
+            (Action::Stop, vec![KeyCode::S]),
+            (Action::CycleFocus, vec![KeyCode::Tab]),
+            (Action::ToggleAttackStyle, vec![KeyCode::R]),
+            (Action::ToggleAutoRetaliate, vec![KeyCode::A]),
+            (Action::BuildMenu, vec![KeyCode::B]),
+            (Action::Cancel, vec![KeyCode::Escape]),
+            (Action::Pause, vec![KeyCode::Escape]),
+            (Action::ToggleToonShading, vec![KeyCode::T]),
+            (Action::ToggleFrameBudget, vec![KeyCode::F12]),
+            (Action::ToggleContextMenu, vec![KeyCode::M]),
+            (Action::ToggleEditor, vec![KeyCode::F11]),
+            (Action::QuickSave, vec![KeyCode::F5]),
+            (Action::QuickLoad, vec![KeyCode::F9]),
+            (
+                Action::ToggleStatusPlates,
+                vec![KeyCode::LAlt, KeyCode::RAlt],
+            ),
+            (Action::AddModifier, vec![KeyCode::LShift, KeyCode::RShift]),
+            (
+                Action::ControlModifier,
+                vec![KeyCode::LControl, KeyCode::RControl],
+            ),
+        ]);
+
+        let bookmark_keys = [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4];
+        for (slot, key) in bookmark_keys.into_iter().enumerate() {
+            bindings.insert(Action::CameraBookmark(slot), vec![key]);
+        }
+
+        let group_keys = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ];
+        for (group, key) in group_keys.into_iter().enumerate() {
+            bindings.insert(Action::ControlGroup(group), vec![key]);
+        }
+
+        Self(bindings)
+    }
+}
+
+impl InputBindings {
+    /// Name of the first key bound to `action`, for showing on buttons.
+    pub fn key_name(&self, action: Action) -> Option<String> {
+        let key = self.0.get(&action)?.first()?;
+        Some(format!("{:?}", key))
+    }
+}
+
+/// Actions fired this frame by something other than a key, like a command
+/// card button. They count as pressed for the rest of the frame.
+#[derive(Resource, Default)]
+pub struct TriggeredActions(pub HashSet<Action>);
+
+fn clear_triggered_actions(mut triggered: ResMut<TriggeredActions>) {
+    if !triggered.0.is_empty() {
+        triggered.0.clear();
+    }
+}
+
+/// Keyboard state looked up through `InputBindings`, plus any
+/// `TriggeredActions`.
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    bindings: Res<'w, InputBindings>,
+    keys: Res<'w, Input<KeyCode>>,
+    triggered: Res<'w, TriggeredActions>,
+}
+
+impl ActionInput<'_> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.triggered.0.contains(&action)
+            || self.bound_keys(action).any(|key| self.keys.pressed(key))
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.triggered.0.contains(&action)
+            || self
+                .bound_keys(action)
+                .any(|key| self.keys.just_pressed(key))
+    }
+
+    fn bound_keys(&self, action: Action) -> impl Iterator<Item = KeyCode> + '_ {
+        self.bindings.0.get(&action).into_iter().flatten().copied()
+    }
+}
+
+/// Ray from the camera through the cursor, if the cursor is in the window.
+pub fn cursor_ray(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Ray> {
+    window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+}
+
+/// Distance along a cursor ray between terrain height checks, in world
+/// units.
+const GROUND_RAY_STEP: f32 = 0.25;
+
+/// Where a ray first hits the terrain, if it does. Off the map it hits the
+/// plane at height 0 instead.
+pub fn ground_point(ray: Ray, tile_map: &TileMap) -> Option<Vec3> {
+    // Step down the ray from where it drops below the highest point of the
+    // map until it is under the terrain, then narrow down the crossing
+    let top = ray.intersect_plane(Vec3::Y * tile_map.max_height(), Vec3::Y)?;
+    let bottom = ray.intersect_plane(Vec3::ZERO, Vec3::Y)?;
+    let below = |distance: f32| {
+        let point = ray.get_point(distance);
+        point.y <= tile_map.height_at(point)
+    };
+
+    let mut above = top;
+    let mut distance = top;
+    while distance < bottom {
+        distance = (distance + GROUND_RAY_STEP).min(bottom);
+        if below(distance) {
+            break;
+        }
+        above = distance;
+    }
+    for _ in 0..8 {
+        let middle = (above + distance) / 2.0;
+        if below(middle) {
+            distance = middle;
+        } else {
+            above = middle;
+        }
+    }
+    Some(ray.get_point(distance))
+}
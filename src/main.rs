@@ -1,14 +1,127 @@
 //! A simple 3D scene with light shining over a osrs player model.
 //! Simple animation control and camera movement.
 //!
+//! Run with `--map <file>` to play a map file (see `assets/maps`) instead of a
+//! generated world, and with `--seed <number>` to generate the same world again.
+//! `--replay <file>` watches back a recorded game, such as `replays/last.ron`.
+//!
 //! Controls:
-//! - Mouse: Left click to select player, right click to move player
-//! - arrows/mouse: move camera
-
-use bevy::input::mouse::{MouseButtonInput, MouseWheel};
+//! - Mouse: Left click to select player (Shift to add to the selection, Ctrl to toggle,
+//!   double click for all units of that type on screen),
+//!   right click to move player, attack an enemy or help build a construction site
+//!   (Shift to queue it after the current orders)
+//! - arrows/mouse: move camera, middle mouse drag: pan, mouse wheel: zoom
+//! - Q/E: rotate camera
+//! - Home: center camera on selected units
+//! - Ctrl+F1–F4: bookmark the camera position, F1–F4: jump back to it
+//! - Minimap: left click or drag to move the camera, right click to move units there,
+//!   scroll to zoom, drag the bar above it to move it or its corner to resize it,
+//!   N: keep north up
+//! - Ctrl+1–9: assign selected units to a control group, 1–9: select it, twice to
+//!   center the camera on it
+//! - S: stop selected units and drop their queued orders
+//! - Chatbox (bottom left): click the tabs to filter messages, scroll to read older ones
+//! - Command card (bottom centre): buttons for the orders the selected units can be given
+//! - Period: select the next idle worker
+//! - Tab: cycle which selected unit is focused
+//! - Space: jump the camera to recent events (units attacked, buildings finished)
+//! - R: switch selected units between melee and ranged attacks
+//! - A: toggle auto-retaliate for selected units
+//! - B: open the build menu, left click to place, Escape to cancel
+//! - T: toggle flat colors with outlines
+//! - F12: toggle frame budget warnings
+//! - Escape: pause and open the menu (after closing any open menu)
+//! - F5: quicksave, F9: quickload
+//! - M: toggle an OSRS-style right click menu instead of instant orders
+//! - Alt: toggle the status plates above units
+//! - F11: switch to the map editor and back; pick a tool on the toolbar, then left click
+//!   on the map to paint tiles, put up structures, add start positions or erase them
+
+// Bevy systems take the data they use as parameters and their queries as
+// tuple types, so long argument lists and query types are the norm here.
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use bevy::input::mouse::MouseButtonInput;
 use bevy::input::ButtonState;
-use bevy::{prelude::*, render::camera::ScalingMode};
+use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
+use serde::{Deserialize, Serialize};
+
+mod animation;
+mod autosave;
+mod building;
+mod camera;
+mod chatbox;
+mod combat;
+mod command_card;
+mod command_queue;
+mod context_menu;
+mod control_groups;
+mod doodads;
+mod editor;
+mod focus;
+mod grid;
+mod health;
+mod hitsplat;
+mod hover;
+mod idle_workers;
+mod input;
+mod map_file;
+mod minimap;
+mod movement;
+mod notable_events;
+mod npc;
+mod pause_menu;
+mod profiling;
+mod render_mode;
+mod replay;
+mod rng;
+mod save;
+mod settings;
+mod status_plates;
+mod terrain;
+mod tick;
+mod tooltip;
+mod ui;
+mod water;
+mod worldgen;
+
+use animation::{FidgetAnimations, IdleTime, UnitAnimationPlugin};
+use autosave::AutosavePlugin;
+use building::{construction_site_at, BuildingPlugin, ConstructionSite};
+use camera::{CameraPlugin, MainCamera};
+use chatbox::ChatboxPlugin;
+use combat::{pick_attack_target, CombatPlugin, CombatStats, Faction};
+use command_card::CommandCardPlugin;
+use command_queue::{CommandQueuePlugin, PlayerOrder, UnitCommand};
+use context_menu::{ContextMenuPlugin, ContextMenuSettings};
+use control_groups::ControlGroupsPlugin;
+use doodads::{Doodad, DoodadPlugin};
+use editor::EditorPlugin;
+use focus::FocusPlugin;
+use grid::{tile_at, Footprint, OccupancyGrid};
+use health::{Health, HealthPlugin};
+use hitsplat::HitsplatPlugin;
+use hover::HoverPlugin;
+use idle_workers::IdleWorkersPlugin;
+use input::{ground_point, Action, ActionInput, InputBindingsPlugin};
+use map_file::MapData;
+use minimap::{MinimapPlugin, MinimapViewOutline};
+use movement::{reserve_step, step_blocked, Blocked, MovementConfig, MovementPlugin, MAX_WAIT};
+use notable_events::NotableEventsPlugin;
+use npc::NpcPlugin;
+use pause_menu::PauseMenuPlugin;
+use profiling::{ProfilingPlugin, SystemTimings};
+use render_mode::RenderModePlugin;
+use replay::ReplayPlugin;
+use save::SavePlugin;
+use settings::SettingsFilePlugin;
+use status_plates::StatusPlatesPlugin;
+use terrain::{TerrainPlugin, TileMap};
+use tick::{Interpolated, TickPlugin};
+use tooltip::TooltipPlugin;
+use ui::{GameUiPlugin, PointerOverUi};
+use water::WaterPlugin;
 
 fn main() {
     App::new()
@@ -19,18 +132,110 @@ fn main() {
                 .disable::<DebugPickingPlugin>()
                 .disable::<DefaultHighlightingPlugin>(),
         )
-        .init_resource::<GameData>()
+        .add_state::<GameState>()
+        .configure_set(Gameplay.run_if(in_state(GameState::InGame)))
+        // The simulation systems in the set run on the fixed tick
+        .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+            schedule.configure_set(Gameplay.run_if(in_state(GameState::InGame)));
+        })
+        .configure_set(
+            MapView.run_if(in_state(GameState::InGame).or_else(in_state(GameState::Editor))),
+        )
+        .add_plugin(InputBindingsPlugin)
+        .add_plugin(TickPlugin)
+        // Before the terrain, which is generated from the replay's seed
+        .add_plugin(ReplayPlugin)
+        .add_plugin(TerrainPlugin)
+        .add_plugin(WaterPlugin)
+        .add_plugin(DoodadPlugin)
+        .add_plugin(MovementPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(NotableEventsPlugin)
+        .add_plugin(UnitAnimationPlugin)
+        .add_plugin(RenderModePlugin)
+        .add_plugin(GameUiPlugin)
+        .add_plugin(TooltipPlugin)
+        .add_plugin(ChatboxPlugin)
+        .add_plugin(ProfilingPlugin)
+        .add_plugin(HealthPlugin)
+        .add_plugin(CombatPlugin)
+        .add_plugin(HitsplatPlugin)
+        .add_plugin(NpcPlugin)
+        .add_plugin(BuildingPlugin)
+        .add_plugin(CommandQueuePlugin)
+        .add_plugin(ContextMenuPlugin)
+        .add_plugin(HoverPlugin)
+        .add_plugin(StatusPlatesPlugin)
+        .add_plugin(CommandCardPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_plugin(ControlGroupsPlugin)
+        .add_plugin(IdleWorkersPlugin)
+        .add_plugin(FocusPlugin)
+        .add_plugin(PauseMenuPlugin)
+        .add_plugin(EditorPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(AutosavePlugin)
+        .add_plugin(SettingsFilePlugin)
         .add_startup_system(setup)
-        .add_system(keyboard_camera_movement)
-        .add_system(mouse_camera_movement)
         .add_system(make_pickable)
-        .add_system(set_location_and_start_movement)
-        .add_system(move_entities_to_location)
+        .add_system(set_location_and_start_movement.in_set(Gameplay))
+        .add_system(
+            move_entities_to_location
+                .in_set(Gameplay)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_event::<SelectEvent>()
+        .add_event::<SelectionChanged>()
+        .add_system(
+            select_entities
+                .in_set(MapView)
+                .run_if(on_event::<SelectEvent>()),
+        )
+        .add_system(
+            select_same_type_on_double_click
+                .after(select_entities)
+                .in_set(MapView)
+                .run_if(on_event::<SelectEvent>()),
+        )
         .add_event::<DeselectAllEvent>()
-        .add_system(deselect_all_entities.run_if(on_event::<DeselectAllEvent>()))
+        .add_system(
+            deselect_all_entities
+                .in_set(MapView)
+                .run_if(on_event::<DeselectAllEvent>()),
+        )
         .run();
 }
 
+/// Whether the game is running, paused in the pause menu or in the map
+/// editor.
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+enum GameState {
+    #[default]
+    InGame,
+    Paused,
+    Editor,
+}
+
+/// Systems that simulate the world or react to world input. They only run
+/// while playing, not while paused or in the map editor; UI systems stay
+/// outside this set.
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+struct Gameplay;
+
+/// Systems for looking around the map and selecting things on it. They run
+/// while playing and in the map editor.
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+struct MapView;
+
+/// Width and depth of the default map, in world units.
+const MAP_SIZE: f32 = 20.0;
+
+/// Share of the way to its destination a moving unit covers each tick.
+const MOVE_STEP: f32 = 0.03;
+
+/// How far units stand above the ground, so their feet don't sink into it.
+const UNIT_CLEARANCE: f32 = 0.05;
+
 #[derive(Resource)]
 struct Animations(Vec<Handle<AnimationClip>>);
 
@@ -43,9 +248,37 @@ struct PlayerName(String);
 #[derive(Component)]
 struct Selected {}
 
+/// What kind of unit an entity is, for selecting all units of one type.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum UnitType {
+    Worker,
+    Goblin,
+}
+
+impl UnitType {
+    fn name(&self) -> &'static str {
+        match self {
+            UnitType::Worker => "Worker",
+            UnitType::Goblin => "Goblin",
+        }
+    }
+
+    /// Flavour text shown when the unit is examined.
+    fn examine(&self) -> &'static str {
+        match self {
+            UnitType::Worker => "Hard-working and underpaid.",
+            UnitType::Goblin => "An ugly green creature.",
+        }
+    }
+}
+
 #[derive(Component)]
 struct Moving {}
 
+/// Point a `Moving` entity is walking towards.
+#[derive(Component)]
+struct Destination(Vec3);
+
 #[derive(Bundle)]
 struct PlayerBundle {
     name: PlayerName,
@@ -57,36 +290,18 @@ struct PlayerBundle {
 #[derive(Component)]
 struct Ground;
 
-#[derive(Resource, Default)]
-struct GameData {
-    destination: Vec3,
-}
-
-/// set up a simple 3D scene
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    // plane
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(shape::Plane::from_size(20.0).into()),
-            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-            ..default()
-        },
-        Ground,
-        OnPointer::<Click>::send_event::<DeselectAllEvent>(),
-        PickHighlight,
-    ));
-
-    // default player
-    let mut player_transform = Transform::from_xyz(0.0, 0.05, 0.0);
+/// Spawns a player's worker at `position`.
+fn spawn_worker(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    name: String,
+    position: Vec3,
+) -> Entity {
+    let mut player_transform = Transform::from_translation(position);
     player_transform.scale = Vec3::splat(0.03);
 
     let player = PlayerBundle {
-        name: PlayerName("Player1".to_string()),
+        name: PlayerName(name),
         scene: SceneBundle {
             scene: asset_server.load("player.glb#Scene0"),
             transform: player_transform,
@@ -94,20 +309,48 @@ fn setup(
         },
     };
 
-    commands.spawn((
-        player,
-        Movable {},
-        OnPointer::<Click>::commands_mut(|event, commands| {
-            info!("Player selected!");
-            commands.entity(event.listener).insert(Selected {});
-        }),
-    ));
+    commands
+        .spawn((
+            player,
+            Movable {},
+            IdleTime::default(),
+            Interpolated::default(),
+            Health::new(10),
+            CombatStats::default(),
+            Faction::Player,
+            UnitType::Worker,
+            OnPointer::<Click>::send_event::<SelectEvent>(),
+        ))
+        .id()
+}
+
+/// set up a simple 3D scene
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    map: Res<MapData>,
+    tile_map: Res<TileMap>,
+) {
+    // a player at each of the map's start positions
+    for (index, (x, z)) in map.player_starts.iter().enumerate() {
+        let position = tile_map.ground_position(Vec3::new(*x, 0.0, *z));
+        spawn_worker(
+            &mut commands,
+            &asset_server,
+            format!("Player{}", index + 1),
+            position + Vec3::Y * UNIT_CLEARANCE,
+        );
+    }
 
     // animations
     commands.insert_resource(Animations(vec![
         asset_server.load("player.glb#Animation0"), // Start flying animation
         asset_server.load("player.glb#Animation1"), // Return to idle
     ]));
+    commands.insert_resource(FidgetAnimations(vec![
+        asset_server.load("player.glb#Animation2"),
+        asset_server.load("player.glb#Animation3"),
+    ]));
 
     // light
     commands.spawn(PointLightBundle {
@@ -119,28 +362,25 @@ fn setup(
         transform: Transform::from_xyz(4.0, 8.0, 4.0),
         ..default()
     });
-
-    // camera
-    commands.spawn((
-        Camera3dBundle {
-            projection: OrthographicProjection {
-                scale: 5.0,
-                scaling_mode: ScalingMode::FixedVertical(2.0),
-                ..default()
-            }
-            .into(),
-            transform: Transform::from_xyz(5.0, 5.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
-            ..default()
-        },
-        RaycastPickCamera::default(),
-    ));
 }
 
 /// Makes everything in the scene with a mesh pickable
 fn make_pickable(
     mut commands: Commands,
-    meshes: Query<Entity, (With<Handle<Mesh>>, Without<RaycastPickTarget>)>,
+    // Doodads are only scenery, so clicks go through to the ground, and the
+    // minimap's view outline isn't in the main camera's view at all
+    meshes: Query<
+        Entity,
+        (
+            With<Handle<Mesh>>,
+            Without<RaycastPickTarget>,
+            Without<Doodad>,
+            Without<MinimapViewOutline>,
+        ),
+    >,
+    timings: Res<SystemTimings>,
 ) {
+    let _scope = timings.scope("make_pickable");
     for entity in meshes.iter() {
         commands
             .entity(entity)
@@ -148,114 +388,154 @@ fn make_pickable(
     }
 }
 
-fn keyboard_camera_movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut camera: Query<&mut Transform, With<Camera>>,
-) {
-    for mut transform in camera.iter_mut() {
-        let mut translation = Vec3::ZERO;
-        if keyboard_input.pressed(KeyCode::Left) {
-            translation -= transform.rotation * Vec3::X;
-        }
-        if keyboard_input.pressed(KeyCode::Right) {
-            translation += transform.rotation * Vec3::X;
-        }
-        if keyboard_input.pressed(KeyCode::Up) {
-            translation += transform.rotation * Vec3::Y;
-        }
-        if keyboard_input.pressed(KeyCode::Down) {
-            translation -= transform.rotation * Vec3::Y;
-        }
-        transform.translation += translation * 0.1;
-    }
-}
-
-fn mouse_camera_movement(
-    mut cursor_moved_events: EventReader<CursorMoved>,
-    mut mouse_wheel_events: EventReader<MouseWheel>,
-    mut camera: Query<&mut Transform, With<Camera>>,
-    mut windows: Query<&mut Window>,
-) {
-    for event in cursor_moved_events.iter() {
-        // Camera needs to move when the mouse is near the edge of the screen
-        // 1) First we need to get the size of the window
-        let window = windows.single_mut();
-        let scale_factor = window.resolution.scale_factor() as f32;
-        let physical_width = window.resolution.physical_width() as f32;
-        let physical_height = window.resolution.physical_height() as f32;
-
-        let actual_resolution = Vec2::new(
-            physical_width / scale_factor,
-            physical_height / scale_factor,
-        );
-
-        // 2) Then we need to get the mouse position
-        let mouse_position = event.position;
-
-        // 3) Then we need to get the center of the screen
-        let center = actual_resolution / 2.0;
-
-        // 4) Then we need to get the difference between the mouse position and the center
-        let difference = mouse_position - center;
-
-        // 5) Then we need to scale the difference based on the size of the window
-        let scaled_difference = difference / actual_resolution;
-
-        // 6) Then we need to move the camera based on the difference
-        // 6.1) Only move the camera if the mouse is near the edge of the screen
-        if scaled_difference.x.abs() > 0.48 || scaled_difference.y.abs() > 0.48 {
-            for mut transform in camera.iter_mut() {
-                transform.translation +=
-                    Vec3::new(scaled_difference.x * 0.1, scaled_difference.y * 0.1, 0.0);
-            }
-        }
-    }
-
-    for event in mouse_wheel_events.iter() {
-        // Handle zoom
-        for mut transform in camera.iter_mut() {
-            transform.scale *= 1.0 + -event.y / 20.0;
-        }
-    }
-}
-
 fn set_location_and_start_movement(
-    mut commands: Commands,
     mut mouse_button_input_events: EventReader<MouseButtonInput>,
-    selected_entities: Query<(Entity, &mut Selected)>,
-    ground_query: Query<&Transform, With<Ground>>,
-    query_camera: Query<(&Camera, &GlobalTransform)>,
+    actions: ActionInput,
+    selected_entities: Query<Entity, With<Selected>>,
+    query_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
     windows: Query<&mut Window>,
-    mut animation_players: Query<&mut AnimationPlayer>,
-    animations: Res<Animations>,
+    targets: Query<(Entity, &GlobalTransform, &Faction), With<Health>>,
+    construction_sites: Query<(Entity, &Footprint), With<ConstructionSite>>,
+    pointer_over_ui: Res<PointerOverUi>,
+    context_menu: Res<ContextMenuSettings>,
+    tile_map: Res<TileMap>,
+    mut orders: EventWriter<PlayerOrder>,
 ) {
+    // The context menu offers these orders instead
+    if context_menu.enabled {
+        return;
+    }
+
     for event in mouse_button_input_events.iter() {
         if event.button == MouseButton::Right
             && event.state == ButtonState::Pressed
-            && selected_entities.iter().count() > 0
+            && !pointer_over_ui.0
+            && !selected_entities.is_empty()
         {
             let (camera, camera_transform) = query_camera.single();
-            let ground = ground_query.single();
 
             let Some(cursor_position) = windows.single().cursor_position() else { return; };
 
             // Calculate a ray pointing from the camera into the world based on the cursor's position.
             let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else { return; };
 
-            // Calculate if and where the ray is hitting the ground plane.
-            let Some(distance) = ray.intersect_plane(ground.translation, ground.up()) else { return; };
-            let point = ray.get_point(distance);
+            // Attack an enemy if one is under the cursor
+            let enemies = targets
+                .iter()
+                .filter(|(_, _, faction)| **faction != Faction::Player)
+                .map(|(entity, transform, _)| (entity, transform.translation()));
+            let command = if let Some(target) = pick_attack_target(ray, enemies) {
+                UnitCommand::Attack(target)
+            } else {
+                // Calculate if and where the ray is hitting the terrain.
+                let Some(point) = ground_point(ray, &tile_map) else { return; };
+
+                // Help build a construction site if one was clicked
+                match construction_site_at(point, construction_sites.iter()) {
+                    Some(site) => UnitCommand::Build(site),
+                    // Clicks on water go to the shore
+                    None => match tile_map.nearest_walkable(point) {
+                        Some(point) => UnitCommand::Move(point),
+                        None => return,
+                    },
+                }
+            };
+
+            // Shift queues the order after the ones already given
+            orders.send(PlayerOrder::Command {
+                units: selected_entities.iter().collect(),
+                command,
+                queued: actions.pressed(Action::AddModifier),
+            });
+        }
+    }
+}
+
+/// A unit was clicked. Replaces the selection with it, adds it to the
+/// selection while Shift is held, or toggles it while Ctrl is held.
+struct SelectEvent(Entity);
+
+impl From<ListenedEvent<Click>> for SelectEvent {
+    fn from(event: ListenedEvent<Click>) -> Self {
+        SelectEvent(event.listener)
+    }
+}
 
-            commands.insert_resource(GameData { destination: point });
+/// Sent whenever units are added to or removed from the selection.
+struct SelectionChanged;
 
-            for (entity, _) in selected_entities.iter() {
-                commands.entity(entity).insert(Moving {});
+fn select_entities(
+    mut commands: Commands,
+    mut events: EventReader<SelectEvent>,
+    selected: Query<Entity, With<Selected>>,
+    actions: ActionInput,
+    mut selection_changed: EventWriter<SelectionChanged>,
+) {
+    let additive = actions.pressed(Action::AddModifier);
+    let toggle = actions.pressed(Action::ControlModifier);
+
+    for SelectEvent(clicked) in events.iter() {
+        if toggle {
+            if selected.contains(*clicked) {
+                commands.entity(*clicked).remove::<Selected>();
+            } else {
+                commands.entity(*clicked).insert(Selected {});
+            }
+        } else {
+            if !additive {
+                for entity in selected.iter().filter(|entity| entity != clicked) {
+                    commands.entity(entity).remove::<Selected>();
+                }
             }
+            info!("{:?} selected!", clicked);
+            commands.entity(*clicked).insert(Selected {});
+        }
+        selection_changed.send(SelectionChanged);
+    }
+}
+
+/// Seconds within which a second click on the same unit counts as a double
+/// click.
+const DOUBLE_CLICK_WINDOW: f32 = 0.3;
+
+/// Double-clicking a unit selects every unit of the player's of the same type
+/// that is on screen.
+fn select_same_type_on_double_click(
+    mut commands: Commands,
+    mut events: EventReader<SelectEvent>,
+    mut last_click: Local<Option<(Entity, f32)>>,
+    time: Res<Time>,
+    units: Query<(Entity, &UnitType, &Faction, &GlobalTransform)>,
+    query_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut selection_changed: EventWriter<SelectionChanged>,
+) {
+    let (camera, camera_transform) = query_camera.single();
+    let now = time.elapsed_seconds();
 
-            for mut player in animation_players.iter_mut() {
-                player.play(animations.0[0].clone_weak());
+    for SelectEvent(clicked) in events.iter() {
+        let double_click = matches!(
+            *last_click,
+            Some((entity, at)) if entity == *clicked && now - at <= DOUBLE_CLICK_WINDOW
+        );
+        *last_click = Some((*clicked, now));
+        if !double_click {
+            continue;
+        }
+        let Ok((_, clicked_type, ..)) = units.get(*clicked) else { continue; };
+        let Some(viewport) = camera.logical_viewport_rect() else { continue; };
+
+        for (entity, unit_type, faction, transform) in units.iter() {
+            let on_screen = camera
+                .world_to_viewport(camera_transform, transform.translation())
+                .is_some_and(|position| {
+                    position.cmpge(viewport.0).all() && position.cmple(viewport.1).all()
+                });
+            if unit_type == clicked_type && *faction == Faction::Player && on_screen {
+                commands.entity(entity).insert(Selected {});
             }
         }
+        *last_click = None;
+        selection_changed.send(SelectionChanged);
     }
 }
 
@@ -271,23 +551,62 @@ fn deselect_all_entities(
     mut commands: Commands,
     query: Query<(Entity, &Selected)>,
     mouse_button_input: Res<Input<MouseButton>>,
+    actions: ActionInput,
+    pointer_over_ui: Res<PointerOverUi>,
+    mut selection_changed: EventWriter<SelectionChanged>,
 ) {
-    if mouse_button_input.just_released(MouseButton::Left) {
+    // Shift-clicking the ground keeps the selection, like Shift-clicking units
+    let additive = actions.pressed(Action::AddModifier);
+    if mouse_button_input.just_released(MouseButton::Left) && !pointer_over_ui.0 && !additive {
         for (entity, _) in query.iter() {
             commands.entity(entity).remove::<Selected>();
         }
+        selection_changed.send(SelectionChanged);
     }
 }
 
 fn move_entities_to_location(
-    mut query: Query<(&mut Transform, &Moving, &Movable, Entity)>,
+    mut query: Query<(
+        &mut Transform,
+        &Destination,
+        &Moving,
+        &Movable,
+        Option<&Blocked>,
+        Entity,
+    )>,
     mut commands: Commands,
-    game_data: ResMut<GameData>,
-    mut animation_players: Query<&mut AnimationPlayer>,
-    animations: Res<Animations>,
+    timings: Res<SystemTimings>,
+    tile_map: Res<TileMap>,
+    config: Res<MovementConfig>,
+    mut grid: ResMut<OccupancyGrid>,
+    fixed_time: Res<FixedTime>,
 ) {
-    for (mut transform, _, _, entity) in query.iter_mut() {
-        let destination = game_data.destination;
+    let _scope = timings.scope("move_entities_to_location");
+
+    for (mut transform, destination, _, _, blocked, entity) in query.iter_mut() {
+        let destination = destination.0;
+
+        // Wait for the tile ahead to be free when units can't share tiles
+        if config.one_unit_per_tile {
+            if let Some(tile) = reserve_step(&mut grid, entity, transform.translation, destination)
+            {
+                let waited =
+                    blocked.map_or(0.0, |blocked| blocked.0) + fixed_time.period.as_secs_f32();
+                // Stop beside a unit standing on the destination, and give up
+                // on moves that stay blocked
+                if tile == tile_at(destination) || waited > MAX_WAIT {
+                    commands
+                        .entity(entity)
+                        .remove::<(Moving, Destination, Blocked)>();
+                } else {
+                    commands.entity(entity).insert(Blocked(waited));
+                }
+                continue;
+            }
+        }
+        if blocked.is_some() {
+            commands.entity(entity).remove::<Blocked>();
+        }
 
         // Rotate the player to face the point
         let direction = destination - transform.translation;
@@ -300,12 +619,20 @@ fn move_entities_to_location(
 
         // if player is near the destination, just set the position
         if transform.translation.distance(new_point) < 0.1 {
-            commands.entity(entity).remove::<Moving>();
-            for mut player in animation_players.iter_mut() {
-                player.play(animations.0[1].clone_weak());
-            }
+            commands.entity(entity).remove::<(Moving, Destination)>();
         } else {
-            transform.translation = transform.translation.lerp(new_point, 0.01);
+            let next_point = transform.translation.lerp(new_point, MOVE_STEP);
+            // Stop at the edge of ground units can't walk on, such as a lake
+            // or a cliff, and in front of structures
+            if step_blocked(&grid, transform.translation, next_point) {
+                commands
+                    .entity(entity)
+                    .remove::<(Moving, Destination, Blocked)>();
+                continue;
+            }
+            transform.translation = next_point;
+            // Follow the ground over hills and into valleys
+            transform.translation.y = tile_map.height_at(transform.translation) + UNIT_CLEARANCE;
         }
     }
 }
@@ -0,0 +1,264 @@
+//! Map files: RON files describing the tiles, ground heights, structures
+//! and player start positions of a map. Start the game with
+//! `--map <file>` to play one instead of a freshly generated world.
+//!
+//! Tiles are written as one string per row, one character per tile:
+//! `.` grass, `T` forest, `^` hills, `~` water and `#` road.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use rand::Rng;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::building::BuildingKind;
+use crate::terrain::{TileKind, TileMap};
+use crate::worldgen::WorldGenerator;
+
+/// Everything a map puts in the world when the game starts. Loaded from a
+/// map file, or describing the generated world when there is none.
+#[derive(Resource, Serialize, Deserialize)]
+pub struct MapData {
+    /// Tile with the lowest x and z coordinates.
+    pub origin: (i32, i32),
+    /// Rows of tiles from the lowest z up.
+    pub tiles: Vec<String>,
+    /// Corner heights row by row, one more row and column than `tiles`.
+    /// Flat when left out.
+    #[serde(default)]
+    pub heights: Vec<Vec<f32>>,
+    /// Finished structures standing when the map loads.
+    #[serde(default)]
+    pub buildings: Vec<MapBuilding>,
+    /// Ground positions (x, z) of the player's starting workers.
+    pub player_starts: Vec<(f32, f32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MapBuilding {
+    pub kind: BuildingKind,
+    /// Tile under the structure's corner with the lowest coordinates.
+    pub tile: (i32, i32),
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+    UnknownTile(char),
+    RaggedRows,
+    WrongHeightCount,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Io(error) => write!(f, "couldn't read the map file: {}", error),
+            MapError::Parse(error) => write!(f, "couldn't parse the map file: {}", error),
+            MapError::Write(error) => write!(f, "couldn't write the map file: {}", error),
+            MapError::UnknownTile(symbol) => write!(f, "unknown tile '{}'", symbol),
+            MapError::RaggedRows => write!(f, "rows of tiles differ in length"),
+            MapError::WrongHeightCount => {
+                write!(f, "heights need one more row and column than tiles")
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for MapError {
+    fn from(error: std::io::Error) -> Self {
+        MapError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for MapError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        MapError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for MapError {
+    fn from(error: ron::Error) -> Self {
+        MapError::Write(error)
+    }
+}
+
+impl MapData {
+    pub fn load(path: &Path) -> Result<Self, MapError> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        // One line per row of tiles or heights, and per structure
+        let config = PrettyConfig::new().depth_limit(2);
+        let text = ron::ser::to_string_pretty(self, config)?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Map data for an existing tilemap.
+    pub fn from_tile_map(
+        tile_map: &TileMap,
+        buildings: Vec<MapBuilding>,
+        player_starts: Vec<(f32, f32)>,
+    ) -> Self {
+        let origin = tile_map.origin();
+        let size = tile_map.size().as_ivec2();
+        let tiles = (0..size.y)
+            .map(|y| {
+                (0..size.x)
+                    .filter_map(|x| tile_map.get(origin + IVec2::new(x, y)))
+                    .map(|kind| kind.symbol())
+                    .collect()
+            })
+            .collect();
+        let heights = (0..=size.y)
+            .map(|y| {
+                (0..=size.x)
+                    .map(|x| tile_map.corner_height(origin + IVec2::new(x, y)))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            origin: origin.into(),
+            tiles,
+            heights,
+            buildings,
+            player_starts,
+        }
+    }
+
+    pub fn tile_map(&self) -> Result<TileMap, MapError> {
+        let width = self.tiles.first().map_or(0, |row| row.chars().count());
+        if self.tiles.iter().any(|row| row.chars().count() != width) {
+            return Err(MapError::RaggedRows);
+        }
+        let origin = IVec2::from(self.origin);
+        let size = UVec2::new(width as u32, self.tiles.len() as u32);
+
+        let mut tile_map = TileMap::new(origin, size, TileKind::Grass);
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, symbol) in row.chars().enumerate() {
+                let kind = TileKind::from_symbol(symbol).ok_or(MapError::UnknownTile(symbol))?;
+                tile_map.set(origin + IVec2::new(x as i32, y as i32), kind);
+            }
+        }
+
+        if !self.heights.is_empty() {
+            if self.heights.len() != self.tiles.len() + 1
+                || self.heights.iter().any(|row| row.len() != width + 1)
+            {
+                return Err(MapError::WrongHeightCount);
+            }
+            for (y, row) in self.heights.iter().enumerate() {
+                for (x, height) in row.iter().enumerate() {
+                    tile_map.set_corner_height(origin + IVec2::new(x as i32, y as i32), *height);
+                }
+            }
+        }
+
+        Ok(tile_map)
+    }
+}
+
+/// The map file passed with `--map`, if any.
+pub fn map_path_from_args() -> Option<PathBuf> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--map");
+    args.next()?;
+    args.next().map(PathBuf::from)
+}
+
+/// The map chosen on the command line and its tilemap, or a freshly
+/// generated world if there is none or it can't be loaded.
+pub fn load_or_generate_map(generator: &WorldGenerator, rng: &mut impl Rng) -> (MapData, TileMap) {
+    if let Some(path) = map_path_from_args() {
+        match MapData::load(&path).and_then(|map| Ok((map.tile_map()?, map))) {
+            Ok((tile_map, map)) => return (map, tile_map),
+            Err(error) => error!("Can't load map {}: {}", path.display(), error),
+        }
+    }
+
+    let tile_map = generator.generate(rng);
+    let map = MapData::from_tile_map(&tile_map, Vec::new(), vec![(0.0, 0.0)]);
+    (map, tile_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"(
+        origin: (-2, -1),
+        tiles: [
+            ".T^",
+            "~#.",
+        ],
+        heights: [
+            [0.0, 0.5, 1.0, 1.5],
+            [0.0, 0.25, 0.5, 0.75],
+            [1.0, 1.0, 1.0, 1.0],
+        ],
+        player_starts: [(0.5, -0.5)],
+    )"#;
+
+    #[test]
+    fn map_file_round_trip() {
+        let map: MapData = ron::from_str(MAP).unwrap();
+        let tile_map = map.tile_map().unwrap();
+        assert_eq!(tile_map.origin(), IVec2::new(-2, -1));
+        assert_eq!(tile_map.size(), UVec2::new(3, 2));
+        assert_eq!(tile_map.get(IVec2::new(-2, 0)), Some(TileKind::Water));
+
+        let written = MapData::from_tile_map(&tile_map, Vec::new(), map.player_starts.clone());
+        assert_eq!(written.origin, map.origin);
+        assert_eq!(written.tiles, map.tiles);
+        assert_eq!(written.heights, map.heights);
+        assert_eq!(written.player_starts, map.player_starts);
+    }
+
+    #[test]
+    fn flat_when_heights_are_left_out() {
+        let map: MapData =
+            ron::from_str(r#"(origin: (0, 0), tiles: ["..", ".."], player_starts: [])"#).unwrap();
+        let tile_map = map.tile_map().unwrap();
+        assert_eq!(tile_map.max_height(), 0.0);
+    }
+
+    #[test]
+    fn rejects_bad_maps() {
+        let ragged = MapData {
+            origin: (0, 0),
+            tiles: vec!["..".to_string(), ".".to_string()],
+            heights: Vec::new(),
+            buildings: Vec::new(),
+            player_starts: Vec::new(),
+        };
+        assert!(matches!(ragged.tile_map(), Err(MapError::RaggedRows)));
+
+        let unknown = MapData {
+            tiles: vec![".?".to_string()],
+            ..ragged
+        };
+        assert!(matches!(
+            unknown.tile_map(),
+            Err(MapError::UnknownTile('?'))
+        ));
+
+        let short_heights = MapData {
+            tiles: vec!["..".to_string()],
+            heights: vec![vec![0.0; 3]],
+            ..unknown
+        };
+        assert!(matches!(
+            short_heights.tile_map(),
+            Err(MapError::WrongHeightCount)
+        ));
+    }
+}
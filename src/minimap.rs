@@ -0,0 +1,794 @@
+//! The minimap: a top-down camera renders the whole map into a texture shown
+//! in the bottom-right corner, with a dot for each unit on top: white for the
+//! player's, red for hostile ones. An outline marks the ground the main
+//! camera shows. Clicking or dragging on it moves the main
+//! camera there, and right-clicking sends the selected units there.
+//!
+//! The minimap turns with the camera so that up on the minimap is the way the
+//! camera faces, with a compass marking north. N switches to keeping north up
+//! instead. Scrolling over the minimap zooms in on the area around the camera.
+//!
+//! The bar above the minimap moves it around, and the grip in its top-left
+//! corner resizes it.
+
+use std::f32::consts::PI;
+
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy::prelude::*;
+use bevy::render::camera::{RenderTarget, ScalingMode};
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_resource::{
+    Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy::render::view::{NoFrustumCulling, RenderLayers};
+use bevy::ui::RelativeCursorPosition;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{camera_focus, CameraJump, CameraMovement, MainCamera};
+use crate::combat::{AttackTask, Faction};
+use crate::command_queue::{PlayerOrder, UnitCommand};
+use crate::input::{ground_point, Action, ActionInput};
+use crate::terrain::TileMap;
+use crate::ui::UiFont;
+use crate::{Gameplay, MapView, Selected};
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MinimapSettings>()
+            .init_resource::<MinimapProjection>()
+            .add_startup_system(spawn_minimap)
+            .add_system(toggle_north_up.in_set(MapView))
+            .add_system(zoom_minimap.in_set(MapView))
+            .add_system(
+                update_minimap_projection
+                    .after(toggle_north_up)
+                    .after(zoom_minimap)
+                    .after(CameraMovement),
+            )
+            .add_system(spawn_minimap_dots)
+            .add_system(
+                update_minimap_dots
+                    .after(spawn_minimap_dots)
+                    .after(update_minimap_projection),
+            )
+            .add_system(update_compass.after(update_minimap_projection))
+            .add_system(update_view_outline.after(update_minimap_projection))
+            .add_system(start_minimap_pulses)
+            .add_system(pulse_minimap_dots.after(start_minimap_pulses))
+            .add_system(minimap_click.in_set(MapView))
+            .add_system(minimap_move_order.in_set(Gameplay))
+            .add_system(drag_minimap_layout.in_set(MapView))
+            .add_system(apply_minimap_layout.after(drag_minimap_layout));
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MinimapSettings {
+    /// Width and height of the minimap panel, in logical pixels.
+    pub size: f32,
+    /// Distance from the bottom-right corner of the window to the panel's,
+    /// in logical pixels.
+    pub offset: Vec2,
+    /// Width and height of the rendered map texture, in pixels.
+    pub resolution: u32,
+    pub dot_size: f32,
+    /// Seconds a hostile dot pulses for when it starts fighting.
+    pub pulse_duration: f32,
+    /// Keep north at the top instead of turning with the camera.
+    pub north_up: bool,
+    /// How far the minimap is zoomed in. At 1 it shows the whole map, above
+    /// that it shows the area around the camera.
+    pub zoom: f32,
+    pub max_zoom: f32,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            size: 200.0,
+            offset: Vec2::splat(10.0),
+            resolution: 256,
+            dot_size: 6.0,
+            pulse_duration: 0.6,
+            north_up: false,
+            zoom: 1.0,
+            max_zoom: 4.0,
+        }
+    }
+}
+
+/// The top-down camera that renders the map into the minimap texture.
+#[derive(Component)]
+pub struct MinimapCamera;
+
+#[derive(Component)]
+struct Minimap;
+
+#[derive(Component)]
+struct Compass;
+
+/// Outline of the ground the main camera shows, drawn only on the minimap.
+#[derive(Component, Default)]
+pub struct MinimapViewOutline {
+    /// Ground corners of the view as last drawn.
+    corners: [Vec2; 4],
+    width: f32,
+}
+
+/// The bar above the minimap that moves it when dragged.
+#[derive(Component)]
+struct MinimapMoveHandle;
+
+/// The corner of the bar that resizes the minimap when dragged.
+#[derive(Component)]
+struct MinimapResizeGrip;
+
+const HANDLE_HEIGHT: f32 = 12.0;
+const MIN_MINIMAP_SIZE: f32 = 100.0;
+const MAX_MINIMAP_SIZE: f32 = 400.0;
+
+/// Distance of the compass from the centre of the minimap, as a fraction of
+/// its size.
+const COMPASS_RADIUS: f32 = 0.42;
+const COMPASS_FONT_SIZE: f32 = 14.0;
+
+/// Height the minimap camera looks down from.
+const MINIMAP_CAMERA_HEIGHT: f32 = 20.0;
+/// Render layer seen by the minimap camera but not the main one.
+const MINIMAP_ONLY_LAYER: u8 = 1;
+/// Width of the camera view outline, as a fraction of the minimap's size.
+const VIEW_OUTLINE_WIDTH: f32 = 0.012;
+
+#[derive(Component)]
+struct MinimapDot {
+    unit: Entity,
+}
+
+/// Briefly grows and shrinks a dot to draw attention to it.
+#[derive(Component)]
+struct MinimapPulse {
+    timer: Timer,
+}
+
+fn faction_color(faction: Faction) -> Color {
+    match faction {
+        Faction::Player => Color::WHITE,
+        Faction::Hostile => Color::rgb(0.9, 0.15, 0.1),
+    }
+}
+
+/// How the map is laid out on the minimap, following the camera's yaw and
+/// the minimap's zoom.
+///
+/// Minimap positions are fractions of the minimap's size measured from its
+/// top-left corner, matching `RelativeCursorPosition`.
+#[derive(Resource, PartialEq)]
+pub struct MinimapProjection {
+    /// Ground direction that points right on the minimap.
+    right: Vec2,
+    /// Ground direction that points up on the minimap.
+    up: Vec2,
+    /// Ground point in the middle of the minimap.
+    center: Vec2,
+    /// World units across the minimap.
+    extent: f32,
+}
+
+impl FromWorld for MinimapProjection {
+    fn from_world(world: &mut World) -> Self {
+        let map = world.resource::<TileMap>().bounds();
+        Self::new(NORTH, map.center(), 1.0, map)
+    }
+}
+
+/// North in ground coordinates, which is -Z in the world.
+const NORTH: Vec2 = Vec2::NEG_Y;
+
+impl MinimapProjection {
+    /// Projection with `up`, a normalized ground direction, at the top and
+    /// `center` in the middle. At a zoom of 1 the whole of `map` fits.
+    fn new(up: Vec2, center: Vec2, zoom: f32, map: Rect) -> Self {
+        // A square map turned on the minimap needs more room to fit, up to
+        // its diagonal at 45°
+        let full_extent = map.width().max(map.height()) * (up.x.abs() + up.y.abs());
+        Self {
+            right: up.perp(),
+            up,
+            center,
+            extent: full_extent / zoom,
+        }
+    }
+
+    pub fn world_to_minimap(&self, position: Vec3) -> Vec2 {
+        let offset = (Vec2::new(position.x, position.z) - self.center) / self.extent;
+        Vec2::new(offset.dot(self.right), -offset.dot(self.up)) + Vec2::splat(0.5)
+    }
+
+    pub fn minimap_to_world(&self, position: Vec2) -> Vec3 {
+        let offset = (position - Vec2::splat(0.5)) * self.extent;
+        let point = self.center + self.right * offset.x - self.up * offset.y;
+        Vec3::new(point.x, 0.0, point.y)
+    }
+}
+
+fn spawn_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    settings: Res<MinimapSettings>,
+    font: Res<UiFont>,
+    tile_map: Res<TileMap>,
+) {
+    let size = Extent3d {
+        width: settings.resolution,
+        height: settings.resolution,
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("minimap"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    // Looking straight down with north at the top, until the first
+    // `update_minimap_projection` turns it
+    let bounds = tile_map.bounds();
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                order: -1,
+                target: RenderTarget::Image(image.clone()),
+                ..default()
+            },
+            camera_3d: Camera3d {
+                clear_color: ClearColorConfig::Custom(Color::rgb(0.1, 0.15, 0.1)),
+                ..default()
+            },
+            projection: OrthographicProjection {
+                scaling_mode: ScalingMode::Fixed {
+                    width: bounds.width(),
+                    height: bounds.height(),
+                },
+                ..default()
+            }
+            .into(),
+            transform: Transform::from_xyz(
+                bounds.center().x,
+                MINIMAP_CAMERA_HEIGHT,
+                bounds.center().y,
+            )
+            .looking_at(
+                Vec3::new(bounds.center().x, 0.0, bounds.center().y),
+                Vec3::NEG_Z,
+            ),
+            ..default()
+        },
+        UiCameraConfig { show_ui: false },
+        RenderLayers::from_layers(&[0, MINIMAP_ONLY_LAYER]),
+        MinimapCamera,
+    ));
+
+    // Just under the minimap camera, so it is drawn over everything else.
+    // Its mesh changes shape as the camera moves, so its bounds go stale
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(view_outline_mesh(&[Vec2::ZERO; 4], 0.0)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::WHITE,
+                unlit: true,
+                cull_mode: None,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, MINIMAP_CAMERA_HEIGHT - 1.0, 0.0),
+            ..default()
+        },
+        RenderLayers::layer(MINIMAP_ONLY_LAYER),
+        NoFrustumCulling,
+        MinimapViewOutline::default(),
+    ));
+
+    commands
+        .spawn((
+            ImageBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(settings.offset.x),
+                        bottom: Val::Px(settings.offset.y),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(settings.size), Val::Px(settings.size)),
+                    ..default()
+                },
+                image: image.into(),
+                ..default()
+            },
+            Interaction::default(),
+            RelativeCursorPosition::default(),
+            Minimap,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "N",
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: COMPASS_FONT_SIZE,
+                        color: Color::rgb(1.0, 0.85, 0.3),
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    // Centre the letter on its position
+                    margin: UiRect {
+                        left: Val::Px(-COMPASS_FONT_SIZE / 4.0),
+                        top: Val::Px(-COMPASS_FONT_SIZE / 2.0),
+                        ..default()
+                    },
+                    ..default()
+                }),
+                Compass,
+            ));
+
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            left: Val::Px(0.0),
+                            top: Val::Px(-HANDLE_HEIGHT),
+                            ..default()
+                        },
+                        size: Size::new(Val::Percent(100.0), Val::Px(HANDLE_HEIGHT)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.25, 0.22, 0.18).into(),
+                    ..default()
+                },
+                Interaction::default(),
+                MinimapMoveHandle,
+            ));
+
+            // Spawned after the handle so it is on top of it
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            left: Val::Px(0.0),
+                            top: Val::Px(-HANDLE_HEIGHT),
+                            ..default()
+                        },
+                        size: Size::new(Val::Px(HANDLE_HEIGHT), Val::Px(HANDLE_HEIGHT)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.6, 0.5, 0.3).into(),
+                    ..default()
+                },
+                Interaction::default(),
+                MinimapResizeGrip,
+            ));
+        });
+}
+
+fn toggle_north_up(actions: ActionInput, mut settings: ResMut<MinimapSettings>) {
+    if actions.just_pressed(Action::ToggleMinimapNorthUp) {
+        settings.north_up = !settings.north_up;
+    }
+}
+
+/// Scrolling over the minimap zooms it in and out, doubling or halving the
+/// zoom per step.
+fn zoom_minimap(
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut settings: ResMut<MinimapSettings>,
+    minimap: Query<&Interaction, With<Minimap>>,
+) {
+    let hovered = minimap
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+
+    for event in mouse_wheel_events.iter() {
+        if !hovered || event.y == 0.0 {
+            continue;
+        }
+        let zoom = if event.y > 0.0 {
+            settings.zoom * 2.0
+        } else {
+            settings.zoom / 2.0
+        };
+        settings.zoom = zoom.clamp(1.0, settings.max_zoom);
+    }
+}
+
+/// Turns the minimap camera and projection to face the way the main camera
+/// does, or north when `north_up` is set. When zoomed in, the minimap also
+/// follows the camera.
+fn update_minimap_projection(
+    settings: Res<MinimapSettings>,
+    tile_map: Res<TileMap>,
+    mut projection: ResMut<MinimapProjection>,
+    main_camera: Query<&Transform, With<MainCamera>>,
+    mut minimap_camera: Query<
+        (&mut Transform, &mut Projection),
+        (With<MinimapCamera>, Without<MainCamera>),
+    >,
+) {
+    let Ok(main_camera) = main_camera.get_single() else {
+        return;
+    };
+
+    let forward = main_camera.forward();
+    let up = Vec2::new(forward.x, forward.z).normalize_or_zero();
+    let up = if settings.north_up || up == Vec2::ZERO {
+        NORTH
+    } else {
+        up
+    };
+    let center = if settings.zoom > 1.0 {
+        let focus = camera_focus(main_camera);
+        Vec2::new(focus.x, focus.z)
+    } else {
+        tile_map.bounds().center()
+    };
+
+    let new_projection = MinimapProjection::new(up, center, settings.zoom, tile_map.bounds());
+    if new_projection == *projection {
+        return;
+    }
+    *projection = new_projection;
+
+    for (mut transform, mut camera_projection) in minimap_camera.iter_mut() {
+        transform.translation.x = center.x;
+        transform.translation.z = center.y;
+        let target = transform.translation * Vec3::new(1.0, 0.0, 1.0);
+        transform.look_at(target, Vec3::new(up.x, 0.0, up.y));
+        if let Projection::Orthographic(orthographic) = camera_projection.as_mut() {
+            orthographic.scaling_mode = ScalingMode::Fixed {
+                width: projection.extent,
+                height: projection.extent,
+            };
+        }
+    }
+}
+
+/// Keeps the compass on the minimap's edge in the direction of north.
+fn update_compass(
+    projection: Res<MinimapProjection>,
+    mut compass: Query<&mut Style, With<Compass>>,
+) {
+    if !projection.is_changed() {
+        return;
+    }
+
+    let north = Vec2::new(NORTH.dot(projection.right), -NORTH.dot(projection.up));
+    let position = Vec2::splat(0.5) + north * COMPASS_RADIUS;
+    for mut style in compass.iter_mut() {
+        style.position.left = Val::Percent(position.x * 100.0);
+        style.position.top = Val::Percent(position.y * 100.0);
+    }
+}
+
+/// Flat band of `width` around the ground polygon `corners`, one quad per
+/// side, lengthened to close the corners.
+fn view_outline_mesh(corners: &[Vec2], width: f32) -> Mesh {
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+    for (index, &start) in corners.iter().enumerate() {
+        let end = corners[(index + 1) % corners.len()];
+        let along = (end - start).normalize_or_zero() * width / 2.0;
+        let across = along.perp();
+        let first = positions.len() as u32;
+        for point in [
+            start - along - across,
+            start - along + across,
+            end + along + across,
+            end + along - across,
+        ] {
+            positions.push([point.x, 0.0, point.y]);
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|offset| first + offset));
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 1.0, 0.0]; positions.len()],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Redraws the outline where the main camera's view, through the corners of
+/// the screen, meets the ground. The outline is kept the same width on the
+/// minimap at every zoom.
+fn update_view_outline(
+    projection: Res<MinimapProjection>,
+    tile_map: Res<TileMap>,
+    main_camera: Query<(&Camera, &Transform), With<MainCamera>>,
+    mut outline: Query<(&mut MinimapViewOutline, &Handle<Mesh>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let Ok((camera, transform)) = main_camera.get_single() else {
+        return;
+    };
+    let Some(size) = camera.logical_viewport_size() else {
+        return;
+    };
+
+    // This frame's camera position, before it is propagated
+    let camera_transform = GlobalTransform::from(*transform);
+    // Corners above the horizon are taken as far as the whole map away
+    let horizon = tile_map.bounds().size().length();
+    let mut corners = [Vec2::ZERO; 4];
+    for (corner, screen_corner) in corners.iter_mut().zip([
+        Vec2::ZERO,
+        Vec2::new(size.x, 0.0),
+        size,
+        Vec2::new(0.0, size.y),
+    ]) {
+        let Some(ray) = camera.viewport_to_world(&camera_transform, screen_corner) else {
+            return;
+        };
+        let point = ground_point(ray, &tile_map).unwrap_or_else(|| {
+            ray.origin + (ray.direction * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero() * horizon
+        });
+        *corner = Vec2::new(point.x, point.z);
+    }
+    let width = projection.extent * VIEW_OUTLINE_WIDTH;
+
+    for (mut outline, mesh) in outline.iter_mut() {
+        // Only rebuild the mesh when the view changed
+        if outline.corners == corners && outline.width == width {
+            continue;
+        }
+        outline.corners = corners;
+        outline.width = width;
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            *mesh = view_outline_mesh(&corners, width);
+        }
+    }
+}
+
+fn spawn_minimap_dots(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    minimap: Query<Entity, With<Minimap>>,
+    units: Query<(Entity, &Faction), Added<Faction>>,
+) {
+    let Ok(minimap) = minimap.get_single() else {
+        return;
+    };
+
+    for (unit, faction) in units.iter() {
+        let dot = commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(Val::Px(settings.dot_size), Val::Px(settings.dot_size)),
+                        // Centre the dot on its position
+                        margin: UiRect {
+                            left: Val::Px(-settings.dot_size / 2.0),
+                            top: Val::Px(-settings.dot_size / 2.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    background_color: faction_color(*faction).into(),
+                    ..default()
+                },
+                MinimapDot { unit },
+            ))
+            .id();
+        commands.entity(minimap).add_child(dot);
+    }
+}
+
+/// Moves each dot to its unit, hides it while the unit is off the minimap
+/// and removes it once the unit is gone.
+fn update_minimap_dots(
+    mut commands: Commands,
+    projection: Res<MinimapProjection>,
+    units: Query<&GlobalTransform>,
+    mut dots: Query<(Entity, &MinimapDot, &mut Style)>,
+) {
+    for (entity, dot, mut style) in dots.iter_mut() {
+        let Ok(transform) = units.get(dot.unit) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        let position = projection.world_to_minimap(transform.translation());
+        let display = if position.cmpge(Vec2::ZERO).all() && position.cmple(Vec2::ONE).all() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+
+        let left = Val::Percent(position.x * 100.0);
+        let top = Val::Percent(position.y * 100.0);
+        // Only touch the style when the dot moved, so idle units don't
+        // trigger a UI relayout every frame
+        if style.position.left != left || style.position.top != top {
+            style.position.left = left;
+            style.position.top = top;
+        }
+    }
+}
+
+/// Pulses the dot of a hostile unit when it starts fighting one of the
+/// player's units, whichever side started it.
+fn start_minimap_pulses(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    new_attacks: Query<(Entity, &AttackTask), Added<AttackTask>>,
+    factions: Query<&Faction>,
+    dots: Query<(Entity, &MinimapDot)>,
+) {
+    for (attacker, task) in new_attacks.iter() {
+        let hostile = match (factions.get(attacker), factions.get(task.target)) {
+            (Ok(Faction::Hostile), Ok(Faction::Player)) => attacker,
+            (Ok(Faction::Player), Ok(Faction::Hostile)) => task.target,
+            _ => continue,
+        };
+
+        for (entity, dot) in dots.iter() {
+            if dot.unit == hostile {
+                commands.entity(entity).insert(MinimapPulse {
+                    timer: Timer::from_seconds(settings.pulse_duration, TimerMode::Once),
+                });
+            }
+        }
+    }
+}
+
+fn pulse_minimap_dots(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<MinimapSettings>,
+    mut dots: Query<(Entity, &mut MinimapPulse, &mut Style)>,
+) {
+    for (entity, mut pulse, mut style) in dots.iter_mut() {
+        pulse.timer.tick(time.delta());
+
+        // Grow to twice the size and back
+        let growth = (pulse.timer.percent() * PI).sin();
+        let size = settings.dot_size * (1.0 + growth);
+        style.size = Size::new(Val::Px(size), Val::Px(size));
+        style.margin.left = Val::Px(-size / 2.0);
+        style.margin.top = Val::Px(-size / 2.0);
+
+        if pulse.timer.finished() {
+            commands.entity(entity).remove::<MinimapPulse>();
+        }
+    }
+}
+
+fn minimap_click(
+    minimap: Query<(&Interaction, &RelativeCursorPosition), With<Minimap>>,
+    projection: Res<MinimapProjection>,
+    mut jumps: EventWriter<CameraJump>,
+) {
+    for (interaction, cursor) in minimap.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        // Dragging keeps the interaction going even past the minimap's edge
+        if let Some(position) = cursor.normalized {
+            let position = position.clamp(Vec2::ZERO, Vec2::ONE);
+            jumps.send(CameraJump(projection.minimap_to_world(position)));
+        }
+    }
+}
+
+/// Right-clicking the minimap sends the selected units to that spot.
+fn minimap_move_order(
+    mouse_button_input: Res<Input<MouseButton>>,
+    minimap: Query<(&Interaction, &RelativeCursorPosition), With<Minimap>>,
+    projection: Res<MinimapProjection>,
+    tile_map: Res<TileMap>,
+    selected: Query<Entity, With<Selected>>,
+    mut orders: EventWriter<PlayerOrder>,
+) {
+    if !mouse_button_input.just_pressed(MouseButton::Right) {
+        return;
+    }
+
+    for (interaction, cursor) in minimap.iter() {
+        if *interaction == Interaction::None {
+            continue;
+        }
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+
+        let Some(point) = tile_map.nearest_walkable(projection.minimap_to_world(position)) else {
+            continue;
+        };
+        orders.send(PlayerOrder::Command {
+            units: selected.iter().collect(),
+            command: UnitCommand::Move(point),
+            queued: false,
+        });
+    }
+}
+
+/// Moves the minimap while its handle is dragged and resizes it while the
+/// grip is dragged, keeping it on screen.
+fn drag_minimap_layout(
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut settings: ResMut<MinimapSettings>,
+    handle: Query<&Interaction, With<MinimapMoveHandle>>,
+    grip: Query<&Interaction, With<MinimapResizeGrip>>,
+    windows: Query<&Window>,
+) {
+    let delta: Vec2 = mouse_motion_events.iter().map(|event| event.delta).sum();
+    if delta == Vec2::ZERO {
+        return;
+    }
+    let moving = handle
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    let resizing = grip
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !moving && !resizing {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    let mut size = settings.size;
+    let mut offset = settings.offset;
+    if resizing {
+        // The grip is at the top-left corner, so dragging up or left grows
+        // the minimap away from its anchored bottom-right corner
+        size = (size - (delta.x + delta.y) / 2.0).clamp(MIN_MINIMAP_SIZE, MAX_MINIMAP_SIZE);
+    } else {
+        // Mouse motion is in screen space, where y points down
+        offset -= delta;
+    }
+
+    let window_size = Vec2::new(window.width(), window.height());
+    let max_offset = (window_size - Vec2::new(size, size + HANDLE_HEIGHT)).max(Vec2::ZERO);
+    settings.size = size;
+    settings.offset = offset.clamp(Vec2::ZERO, max_offset);
+}
+
+fn apply_minimap_layout(
+    settings: Res<MinimapSettings>,
+    mut minimap: Query<&mut Style, With<Minimap>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for mut style in minimap.iter_mut() {
+        style.position.right = Val::Px(settings.offset.x);
+        style.position.bottom = Val::Px(settings.offset.y);
+        style.size = Size::new(Val::Px(settings.size), Val::Px(settings.size));
+    }
+}
@@ -0,0 +1,202 @@
+//! Movement rules. Units normally walk straight through each other. With
+//! one unit per tile turned on in `MovementConfig`, every unit holds the tile
+//! it stands on in the `OccupancyGrid` and reserves the tile ahead before
+//! stepping onto it, waiting while another unit holds it, so units never end
+//! up stacked on one tile. Units never step onto water, steep slopes or
+//! tiles taken by structures.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{tile_at, OccupancyGrid, TILE_SIZE};
+use crate::{Gameplay, Movable, Moving};
+
+pub struct MovementPlugin;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MovementConfig>()
+            .add_system(
+                hold_standing_tiles
+                    .in_set(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(release_reservations);
+    }
+}
+
+/// How far ahead of itself a moving unit reserves the ground.
+const RESERVE_AHEAD: f32 = 0.5 * TILE_SIZE;
+/// Seconds a unit waits for a tile to free up before giving up on its move.
+pub const MAX_WAIT: f32 = 2.0;
+
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovementConfig {
+    /// Only one unit may stand on each tile, OSRS-style, instead of units
+    /// overlapping freely.
+    pub one_unit_per_tile: bool,
+}
+
+/// Seconds a moving unit has been waiting for a tile another unit holds.
+#[derive(Component)]
+pub struct Blocked(pub f32);
+
+/// Reserves what a unit at `position` walking towards `destination` needs
+/// for its next step: the tile it stands on and the tile just ahead, freeing
+/// any others it held. Returns the tile ahead if another unit holds it, in
+/// which case the unit has to wait.
+pub fn reserve_step(
+    grid: &mut OccupancyGrid,
+    unit: Entity,
+    position: Vec3,
+    destination: Vec3,
+) -> Option<IVec2> {
+    let here = tile_at(position);
+    let mut direction = destination - position;
+    direction.y = 0.0;
+    let ahead = tile_at(position + direction.clamp_length_max(RESERVE_AHEAD));
+
+    // A unit that starts out stacked on another's tile may still walk off it
+    grid.reserve(here, unit);
+    if !grid.reserve(ahead, unit) {
+        return Some(ahead);
+    }
+    grid.release(unit, &[here, ahead]);
+    None
+}
+
+/// Whether a unit stepping from `from` to `to` would walk onto a tile it
+/// can't cross: water, a steep slope or one taken by a structure. A unit
+/// already standing on such a tile, such as one a site was just placed on,
+/// may still walk off it.
+pub fn step_blocked(grid: &OccupancyGrid, from: Vec3, to: Vec3) -> bool {
+    let blocked = |tile| grid.is_occupied(tile) || !grid.is_passable(tile);
+    let (here, next) = (tile_at(from), tile_at(to));
+    here != next && blocked(next) && !blocked(here)
+}
+
+/// Has units that aren't moving hold the tile they stand on, and forgets
+/// all reservations when one unit per tile is turned off.
+fn hold_standing_tiles(
+    mut commands: Commands,
+    config: Res<MovementConfig>,
+    mut grid: ResMut<OccupancyGrid>,
+    units: Query<(Entity, &Transform, Option<&Blocked>), (With<Movable>, Without<Moving>)>,
+) {
+    if !config.one_unit_per_tile {
+        if config.is_changed() {
+            grid.clear_reservations();
+        }
+        return;
+    }
+
+    for (entity, transform, blocked) in units.iter() {
+        let here = tile_at(transform.translation);
+        grid.reserve(here, entity);
+        grid.release(entity, &[here]);
+        // Left over from a move that was stopped while waiting
+        if blocked.is_some() {
+            commands.entity(entity).remove::<Blocked>();
+        }
+    }
+}
+
+fn release_reservations(mut grid: ResMut<OccupancyGrid>, mut removed: RemovedComponents<Movable>) {
+    for entity in removed.iter() {
+        grid.release(entity, &[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ground(x: f32) -> Vec3 {
+        Vec3::new(x, 0.0, 0.5)
+    }
+
+    #[test]
+    fn structures_and_water_block_steps() {
+        let mut grid = OccupancyGrid::default();
+        grid.occupy([IVec2::new(1, 0)]);
+        grid.set_impassable([IVec2::new(-1, 0)]);
+
+        assert!(step_blocked(&grid, ground(0.98), ground(1.01)));
+        assert!(step_blocked(&grid, ground(0.02), ground(-0.01)));
+        // Moving within a free tile, or onto one
+        assert!(!step_blocked(&grid, ground(0.5), ground(0.9)));
+        assert!(!step_blocked(&grid, ground(0.02), ground(0.02) + Vec3::Z));
+    }
+
+    #[test]
+    fn walking_out_of_a_structure() {
+        let mut grid = OccupancyGrid::default();
+        grid.occupy([IVec2::new(0, 0), IVec2::new(1, 0)]);
+
+        assert!(!step_blocked(&grid, ground(0.5), ground(0.9)));
+        assert!(!step_blocked(&grid, ground(0.98), ground(1.01)));
+        assert!(!step_blocked(&grid, ground(0.02), ground(-0.01)));
+    }
+
+    #[test]
+    fn two_units_asking_for_one_tile() {
+        let mut grid = OccupancyGrid::default();
+        let (first, second) = (Entity::from_raw(1), Entity::from_raw(2));
+        let middle = IVec2::new(1, 0);
+
+        // Walking towards each other, both about to step onto the middle tile
+        assert_eq!(
+            reserve_step(&mut grid, first, ground(0.7), ground(4.0)),
+            None
+        );
+        assert_eq!(
+            reserve_step(&mut grid, second, ground(2.3), ground(-2.0)),
+            Some(middle)
+        );
+        // Still blocked on the next try
+        assert_eq!(
+            reserve_step(&mut grid, second, ground(2.3), ground(-2.0)),
+            Some(middle)
+        );
+
+        // Once the first unit has gone, the second may follow
+        grid.release(first, &[]);
+        assert_eq!(
+            reserve_step(&mut grid, second, ground(2.3), ground(-2.0)),
+            None
+        );
+        assert!(!grid.reserve(middle, first));
+    }
+
+    #[test]
+    fn stepping_onto_its_own_reserved_tile() {
+        let mut grid = OccupancyGrid::default();
+        let (unit, other) = (Entity::from_raw(1), Entity::from_raw(2));
+
+        assert_eq!(
+            reserve_step(&mut grid, unit, ground(0.7), ground(4.0)),
+            None
+        );
+        // Now standing on the tile it reserved, with the one it left freed
+        assert_eq!(
+            reserve_step(&mut grid, unit, ground(1.2), ground(4.0)),
+            None
+        );
+        assert!(grid.reserve(IVec2::new(0, 0), other));
+        assert!(!grid.reserve(IVec2::new(1, 0), other));
+    }
+
+    #[test]
+    fn walking_off_a_shared_tile() {
+        let mut grid = OccupancyGrid::default();
+        let (unit, other) = (Entity::from_raw(1), Entity::from_raw(2));
+        assert!(grid.reserve(IVec2::new(0, 0), other));
+
+        assert_eq!(
+            reserve_step(&mut grid, unit, ground(0.7), ground(4.0)),
+            None
+        );
+        assert!(!grid.reserve(IVec2::new(1, 0), other));
+    }
+}
@@ -0,0 +1,79 @@
+//! Recent places worth looking at, like a unit under attack or a finished
+//! building. Systems push into `NotableEvents` and Space cycles the camera
+//! through them, newest first.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::camera::CameraJump;
+use crate::input::{Action, ActionInput};
+use crate::Gameplay;
+
+pub struct NotableEventsPlugin;
+
+impl Plugin for NotableEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NotableEvents>()
+            .add_system(jump_to_notable_event.in_set(Gameplay));
+    }
+}
+
+/// How many events are remembered before the oldest is dropped.
+const MAX_NOTABLE_EVENTS: usize = 8;
+/// A new event this close to the latest one of the same kind replaces it
+/// instead of being queued, so a long fight doesn't flood the queue.
+const MERGE_DISTANCE: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotableEventKind {
+    UnitAttacked,
+    BuildingFinished,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct NotableEvent {
+    pub kind: NotableEventKind,
+    pub position: Vec3,
+}
+
+#[derive(Resource, Default)]
+pub struct NotableEvents {
+    /// Newest first.
+    events: VecDeque<NotableEvent>,
+    /// Index of the event Space jumps to next.
+    next: usize,
+}
+
+impl NotableEvents {
+    pub fn push(&mut self, kind: NotableEventKind, position: Vec3) {
+        let event = NotableEvent { kind, position };
+        self.next = 0;
+
+        if let Some(latest) = self.events.front_mut() {
+            if latest.kind == kind && latest.position.distance(position) < MERGE_DISTANCE {
+                *latest = event;
+                return;
+            }
+        }
+
+        self.events.push_front(event);
+        self.events.truncate(MAX_NOTABLE_EVENTS);
+    }
+}
+
+fn jump_to_notable_event(
+    actions: ActionInput,
+    mut notable_events: ResMut<NotableEvents>,
+    mut jumps: EventWriter<CameraJump>,
+) {
+    if !actions.just_pressed(Action::JumpToEvent) || notable_events.events.is_empty() {
+        return;
+    }
+
+    let index = notable_events.next % notable_events.events.len();
+    let event = notable_events.events[index];
+    debug!("Jumping to {:?} at {}", event.kind, event.position);
+    jumps.send(CameraJump(event.position));
+    notable_events.next = index + 1;
+}
@@ -0,0 +1,257 @@
+//! Hostile NPCs: goblins spawned around the edge of the map that wander
+//! near their spawn point, attack player units that come close and give up
+//! the chase when pulled too far from home.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::combat::{AttackTask, CombatStats, Faction};
+use crate::grid::tile_at;
+use crate::health::Health;
+use crate::rng::GameRng;
+use crate::terrain::TileMap;
+use crate::tick::Interpolated;
+use crate::{Destination, Gameplay, Movable, Moving, UnitType, UNIT_CLEARANCE};
+
+pub struct NpcPlugin;
+
+impl Plugin for NpcPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NpcSpawnConfig>()
+            .init_resource::<GoblinAssets>()
+            .add_startup_system(spawn_goblins)
+            .add_system(
+                wander
+                    .in_set(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                update_aggro
+                    .in_set(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct NpcSpawnConfig {
+    pub goblin_count: usize,
+    /// Distance from the centre of the map at which goblins spawn.
+    pub spawn_distance: f32,
+    /// How far a goblin strays from its spawn point while wandering.
+    pub wander_radius: f32,
+    /// Seconds between wander decisions.
+    pub wander_interval: f32,
+    /// Distance at which a goblin notices player units.
+    pub aggro_radius: f32,
+    /// Distance from home at which a goblin stops chasing and walks back.
+    pub leash_radius: f32,
+}
+
+impl Default for NpcSpawnConfig {
+    fn default() -> Self {
+        Self {
+            goblin_count: 4,
+            spawn_distance: 8.0,
+            wander_radius: 1.5,
+            wander_interval: 4.0,
+            aggro_radius: 3.0,
+            leash_radius: 5.0,
+        }
+    }
+}
+
+/// Makes an idle NPC stroll to random points around `home`.
+#[derive(Component)]
+pub struct Wander {
+    pub home: Vec3,
+    pub radius: f32,
+    timer: Timer,
+}
+
+/// Aggro and leash settings for a hostile NPC.
+#[derive(Component)]
+pub struct Aggro {
+    pub home: Vec3,
+    pub aggro_radius: f32,
+    pub leash_radius: f32,
+}
+
+/// What a hostile NPC is currently doing.
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggroState {
+    /// Wandering around home, looking for player units.
+    #[default]
+    Idle,
+    /// Fighting a player unit through an `AttackTask`.
+    Chasing(Entity),
+    /// Walking back home after being pulled too far, ignoring player units.
+    Leashing,
+}
+
+/// Mesh and material goblins are drawn with.
+#[derive(Resource)]
+pub struct GoblinAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for GoblinAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(
+            shape::Capsule {
+                radius: 0.2,
+                depth: 0.4,
+                ..default()
+            }
+            .into(),
+        );
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(Color::rgb(0.35, 0.55, 0.15).into());
+        Self { mesh, material }
+    }
+}
+
+pub fn spawn_goblins(
+    mut commands: Commands,
+    assets: Res<GoblinAssets>,
+    config: Res<NpcSpawnConfig>,
+    tile_map: Res<TileMap>,
+    mut rng: ResMut<GameRng>,
+) {
+    for i in 0..config.goblin_count {
+        // Spread the goblins evenly around the map edge, with a bit of jitter
+        let angle = TAU * i as f32 / config.goblin_count as f32 + rng.gen_range(-0.3..0.3);
+        let home = tile_map.ground_position(Vec3::new(
+            angle.cos() * config.spawn_distance,
+            0.0,
+            angle.sin() * config.spawn_distance,
+        )) + Vec3::Y * UNIT_CLEARANCE;
+        if !tile_map.is_walkable(tile_at(home)) {
+            continue;
+        }
+
+        spawn_goblin(&mut commands, &assets, &config, home, home);
+    }
+}
+
+/// Spawns a goblin at `position` that wanders around and guards `home`.
+pub fn spawn_goblin(
+    commands: &mut Commands,
+    assets: &GoblinAssets,
+    config: &NpcSpawnConfig,
+    home: Vec3,
+    position: Vec3,
+) -> Entity {
+    commands
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position)),
+            Name::new("Goblin"),
+            Movable {},
+            Interpolated::default(),
+            Health::new(5),
+            CombatStats::default(),
+            Faction::Hostile,
+            UnitType::Goblin,
+            Wander {
+                home,
+                radius: config.wander_radius,
+                timer: Timer::from_seconds(config.wander_interval, TimerMode::Repeating),
+            },
+            Aggro {
+                home,
+                aggro_radius: config.aggro_radius,
+                leash_radius: config.leash_radius,
+            },
+            AggroState::default(),
+        ))
+        .with_children(|parent| {
+            parent.spawn(PbrBundle {
+                mesh: assets.mesh.clone(),
+                material: assets.material.clone(),
+                transform: Transform::from_xyz(0.0, 0.4, 0.0),
+                ..default()
+            });
+        })
+        .id()
+}
+
+pub fn wander(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    tile_map: Res<TileMap>,
+    mut npcs: Query<(Entity, &mut Wander), (Without<Moving>, Without<AttackTask>)>,
+    mut rng: ResMut<GameRng>,
+) {
+    for (entity, mut wander) in npcs.iter_mut() {
+        if !wander.timer.tick(fixed_time.period).just_finished() {
+            continue;
+        }
+
+        let angle = rng.gen_range(0.0..TAU);
+        let distance = rng.gen_range(0.0..wander.radius);
+        let point = wander.home + Vec3::new(angle.cos(), 0.0, angle.sin()) * distance;
+        // Try again next time rather than walking off the map
+        if !tile_map.is_walkable(tile_at(point)) {
+            continue;
+        }
+
+        commands
+            .entity(entity)
+            .insert((Moving {}, Destination(point)));
+    }
+}
+
+fn update_aggro(
+    mut commands: Commands,
+    mut npcs: Query<(
+        Entity,
+        &Transform,
+        &Aggro,
+        &mut AggroState,
+        Option<&AttackTask>,
+        Option<&Moving>,
+    )>,
+    targets: Query<(Entity, &Transform, &Faction), With<Health>>,
+) {
+    for (entity, transform, aggro, mut state, attack_task, moving) in npcs.iter_mut() {
+        match *state {
+            AggroState::Idle => {
+                let position = transform.translation;
+                let nearest_player = targets
+                    .iter()
+                    .filter(|(_, _, faction)| **faction == Faction::Player)
+                    .map(|(target, target_transform, _)| {
+                        (target, target_transform.translation.distance(position))
+                    })
+                    .filter(|(_, distance)| *distance <= aggro.aggro_radius)
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+                if let Some((target, _)) = nearest_player {
+                    commands.entity(entity).insert(AttackTask::new(target));
+                    *state = AggroState::Chasing(target);
+                }
+            }
+            AggroState::Chasing(_) => {
+                if attack_task.is_none() {
+                    // The target died or the task was cancelled
+                    *state = AggroState::Idle;
+                } else if transform.translation.distance(aggro.home) > aggro.leash_radius {
+                    commands
+                        .entity(entity)
+                        .remove::<AttackTask>()
+                        .insert((Moving {}, Destination(aggro.home)));
+                    *state = AggroState::Leashing;
+                }
+            }
+            AggroState::Leashing => {
+                if moving.is_none() {
+                    *state = AggroState::Idle;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,493 @@
+//! The pause menu. Escape pauses the game, which stops every system in the
+//! `Gameplay` set and game time, and opens a menu with Resume, Save, Load,
+//! Load autosave, Settings and Quit. The settings screen has a tab per area
+//! with toggles and -/+ steppers for the settings resources of the other
+//! plugins, which are kept in the settings file between games.
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+use crate::autosave::{latest_autosave, AutosaveConfig};
+use crate::building::BuildMode;
+use crate::camera::CameraSettings;
+use crate::combat::CombatSettings;
+use crate::context_menu::ContextMenuSettings;
+use crate::input::{Action, ActionInput};
+use crate::minimap::MinimapSettings;
+use crate::movement::MovementConfig;
+use crate::render_mode::RenderSettings;
+use crate::save::{LoadGame, SaveGame, SaveSlot};
+use crate::status_plates::StatusPlateSettings;
+use crate::ui::{Popup, UiFont};
+use crate::GameState;
+
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PauseScreen>()
+            // Runs before the Update systems so Escape never both closes a
+            // popup and pauses
+            .add_system(
+                toggle_pause
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem),
+            )
+            .add_system(pause_game.in_schedule(OnEnter(GameState::Paused)))
+            .add_system(resume_game.in_schedule(OnExit(GameState::Paused)))
+            .add_system(press_pause_menu_buttons.run_if(in_state(GameState::Paused)))
+            .add_system(
+                spawn_pause_menu
+                    .after(press_pause_menu_buttons)
+                    .run_if(in_state(GameState::Paused)),
+            );
+    }
+}
+
+const FONT_SIZE: f32 = 18.0;
+const BUTTON_COLOR: Color = Color::rgb(0.25, 0.22, 0.18);
+const BUTTON_HOVERED: Color = Color::rgb(0.36, 0.32, 0.26);
+const BUTTON_ACTIVE: Color = Color::rgb(0.5, 0.42, 0.25);
+
+/// Which screen of the pause menu is showing.
+#[derive(Resource, Clone, Copy, Default, PartialEq, Eq)]
+enum PauseScreen {
+    #[default]
+    Main,
+    Settings(SettingsTab),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettingsTab {
+    Audio,
+    Video,
+    Camera,
+    Gameplay,
+}
+
+impl SettingsTab {
+    const ALL: [SettingsTab; 4] = [
+        SettingsTab::Audio,
+        SettingsTab::Video,
+        SettingsTab::Camera,
+        SettingsTab::Gameplay,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            SettingsTab::Audio => "Audio",
+            SettingsTab::Video => "Video",
+            SettingsTab::Camera => "Camera",
+            SettingsTab::Gameplay => "Gameplay",
+        }
+    }
+
+    fn settings(&self) -> &'static [Setting] {
+        match self {
+            SettingsTab::Audio => &[],
+            SettingsTab::Video => &[Setting::ToonShading, Setting::StatusPlates],
+            SettingsTab::Camera => &[Setting::EdgeScroll, Setting::MinimapNorthUp],
+            SettingsTab::Gameplay => &[
+                Setting::RightClickMenu,
+                Setting::AutoRetaliate,
+                Setting::OneUnitPerTile,
+                Setting::Autosave,
+            ],
+        }
+    }
+
+    fn values(&self) -> &'static [ValueSetting] {
+        match self {
+            SettingsTab::Camera => &[
+                ValueSetting::EdgeScrollMargin,
+                ValueSetting::EdgeScrollSpeed,
+            ],
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Setting {
+    ToonShading,
+    StatusPlates,
+    EdgeScroll,
+    MinimapNorthUp,
+    RightClickMenu,
+    AutoRetaliate,
+    OneUnitPerTile,
+    Autosave,
+}
+
+impl Setting {
+    fn label(&self) -> &'static str {
+        match self {
+            Setting::ToonShading => "Flat colors with outlines",
+            Setting::StatusPlates => "Status plates above units",
+            Setting::EdgeScroll => "Scroll at the screen edges",
+            Setting::MinimapNorthUp => "Keep north up on the minimap",
+            Setting::RightClickMenu => "Right-click opens a menu",
+            Setting::AutoRetaliate => "New units auto-retaliate",
+            Setting::OneUnitPerTile => "One unit per tile",
+            Setting::Autosave => "Autosave every few minutes",
+        }
+    }
+}
+
+/// A setting with a number value, stepped up and down between limits.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ValueSetting {
+    EdgeScrollMargin,
+    EdgeScrollSpeed,
+}
+
+impl ValueSetting {
+    fn label(&self) -> &'static str {
+        match self {
+            ValueSetting::EdgeScrollMargin => "Edge scroll margin",
+            ValueSetting::EdgeScrollSpeed => "Edge scroll speed",
+        }
+    }
+
+    /// Change per press of - or +, and the lowest and highest values.
+    fn range(&self) -> (f32, f32, f32) {
+        match self {
+            ValueSetting::EdgeScrollMargin => (0.01, 0.01, 0.1),
+            ValueSetting::EdgeScrollSpeed => (0.02, 0.02, 0.5),
+        }
+    }
+
+    fn format(&self, value: f32) -> String {
+        match self {
+            // A fraction of the window
+            ValueSetting::EdgeScrollMargin => format!("{:.0}%", value * 100.0),
+            ValueSetting::EdgeScrollSpeed => format!("{:.2}", value),
+        }
+    }
+}
+
+/// The settings resources the settings screen can change.
+#[derive(SystemParam)]
+struct GameSettings<'w> {
+    render: ResMut<'w, RenderSettings>,
+    status_plates: ResMut<'w, StatusPlateSettings>,
+    camera: ResMut<'w, CameraSettings>,
+    minimap: ResMut<'w, MinimapSettings>,
+    context_menu: ResMut<'w, ContextMenuSettings>,
+    combat: ResMut<'w, CombatSettings>,
+    movement: ResMut<'w, MovementConfig>,
+    autosave: ResMut<'w, AutosaveConfig>,
+}
+
+impl GameSettings<'_> {
+    fn get(&self, setting: Setting) -> bool {
+        match setting {
+            Setting::ToonShading => self.render.toon,
+            Setting::StatusPlates => self.status_plates.visible,
+            Setting::EdgeScroll => self.camera.edge_scroll,
+            Setting::MinimapNorthUp => self.minimap.north_up,
+            Setting::RightClickMenu => self.context_menu.enabled,
+            Setting::AutoRetaliate => self.combat.auto_retaliate_by_default,
+            Setting::OneUnitPerTile => self.movement.one_unit_per_tile,
+            Setting::Autosave => self.autosave.enabled,
+        }
+    }
+
+    fn toggle(&mut self, setting: Setting) {
+        let value = match setting {
+            Setting::ToonShading => &mut self.render.toon,
+            Setting::StatusPlates => &mut self.status_plates.visible,
+            Setting::EdgeScroll => &mut self.camera.edge_scroll,
+            Setting::MinimapNorthUp => &mut self.minimap.north_up,
+            Setting::RightClickMenu => &mut self.context_menu.enabled,
+            Setting::AutoRetaliate => &mut self.combat.auto_retaliate_by_default,
+            Setting::OneUnitPerTile => &mut self.movement.one_unit_per_tile,
+            Setting::Autosave => &mut self.autosave.enabled,
+        };
+        *value = !*value;
+    }
+
+    fn value(&self, setting: ValueSetting) -> f32 {
+        match setting {
+            ValueSetting::EdgeScrollMargin => self.camera.edge_scroll_margin,
+            ValueSetting::EdgeScrollSpeed => self.camera.edge_scroll_speed,
+        }
+    }
+
+    /// Moves a setting `steps` steps up, or down if negative, within its
+    /// limits.
+    fn step(&mut self, setting: ValueSetting, steps: i32) {
+        let value = match setting {
+            ValueSetting::EdgeScrollMargin => &mut self.camera.edge_scroll_margin,
+            ValueSetting::EdgeScrollSpeed => &mut self.camera.edge_scroll_speed,
+        };
+        let (step, min, max) = setting.range();
+        // Snap to whole steps, so repeated presses don't drift
+        *value = (((*value / step).round() + steps as f32) * step).clamp(min, max);
+    }
+}
+
+#[derive(Component)]
+struct PauseMenu;
+
+/// The button of the settings tab being shown.
+#[derive(Component)]
+struct ActiveTab;
+
+#[derive(Component, Clone, Copy)]
+enum PauseMenuButton {
+    Resume,
+    Save,
+    Load,
+    LoadAutosave,
+    Settings,
+    Quit,
+    Back,
+    Tab(SettingsTab),
+    Toggle(Setting),
+    Step(ValueSetting, i32),
+}
+
+/// Escape pauses the game unless it has a popup or build placement to
+/// cancel first, and while paused goes back one screen.
+fn toggle_pause(
+    actions: ActionInput,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut screen: ResMut<PauseScreen>,
+    build_mode: Res<BuildMode>,
+    popups: Query<(), With<Popup>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+
+    match (state.0, *screen) {
+        (GameState::InGame, _) => {
+            if build_mode.placing.is_none() && popups.is_empty() {
+                next_state.set(GameState::Paused);
+            }
+        }
+        (GameState::Paused, PauseScreen::Main) => next_state.set(GameState::InGame),
+        (GameState::Paused, PauseScreen::Settings(_)) => *screen = PauseScreen::Main,
+        // Resuming would leave the editor for the game
+        (GameState::Editor, _) => {}
+    }
+}
+
+fn pause_game(mut time: ResMut<Time>, mut screen: ResMut<PauseScreen>) {
+    time.pause();
+    *screen = PauseScreen::Main;
+}
+
+fn resume_game(
+    mut commands: Commands,
+    mut time: ResMut<Time>,
+    menus: Query<Entity, With<PauseMenu>>,
+) {
+    time.unpause();
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+fn press_pause_menu_buttons(
+    mut buttons: Query<
+        (
+            &Interaction,
+            &PauseMenuButton,
+            &mut BackgroundColor,
+            Option<&ActiveTab>,
+        ),
+        Changed<Interaction>,
+    >,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut screen: ResMut<PauseScreen>,
+    mut settings: GameSettings,
+    mut saves: EventWriter<SaveGame>,
+    mut loads: EventWriter<LoadGame>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut background, active) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {}
+            Interaction::Hovered => {
+                *background = BUTTON_HOVERED.into();
+                continue;
+            }
+            Interaction::None => {
+                *background = if active.is_some() {
+                    BUTTON_ACTIVE
+                } else {
+                    BUTTON_COLOR
+                }
+                .into();
+                continue;
+            }
+        }
+
+        match *button {
+            PauseMenuButton::Resume => next_state.set(GameState::InGame),
+            PauseMenuButton::Save => saves.send(SaveGame(SaveSlot::Quicksave)),
+            PauseMenuButton::Load => {
+                loads.send(LoadGame(SaveSlot::Quicksave));
+                next_state.set(GameState::InGame);
+            }
+            PauseMenuButton::LoadAutosave => {
+                let slot = latest_autosave(settings.autosave.slots).unwrap_or(0);
+                loads.send(LoadGame(SaveSlot::Autosave(slot)));
+                next_state.set(GameState::InGame);
+            }
+            PauseMenuButton::Settings => *screen = PauseScreen::Settings(SettingsTab::Video),
+            PauseMenuButton::Quit => exit.send(AppExit),
+            PauseMenuButton::Back => *screen = PauseScreen::Main,
+            PauseMenuButton::Tab(tab) => *screen = PauseScreen::Settings(tab),
+            PauseMenuButton::Toggle(setting) => {
+                settings.toggle(setting);
+                // Rebuild the screen to show the new value
+                screen.set_changed();
+            }
+            PauseMenuButton::Step(setting, steps) => {
+                settings.step(setting, steps);
+                screen.set_changed();
+            }
+        }
+    }
+}
+
+/// Rebuilds the menu whenever the screen changes.
+fn spawn_pause_menu(
+    mut commands: Commands,
+    screen: Res<PauseScreen>,
+    font: Res<UiFont>,
+    settings: GameSettings,
+    menus: Query<Entity, With<PauseMenu>>,
+) {
+    if !screen.is_changed() {
+        return;
+    }
+
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    let text_style = TextStyle {
+        font: font.0.clone(),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+    let button = |parent: &mut ChildBuilder, label: &str, action: PauseMenuButton, active: bool| {
+        let mut button = parent.spawn((
+            ButtonBundle {
+                style: Style {
+                    margin: UiRect::all(Val::Px(4.0)),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: if active { BUTTON_ACTIVE } else { BUTTON_COLOR }.into(),
+                ..default()
+            },
+            action,
+        ));
+        if active {
+            button.insert(ActiveTab);
+        }
+        button.with_children(|button| {
+            button.spawn(TextBundle::from_section(label, text_style.clone()));
+        });
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                z_index: ZIndex::Global(50),
+                ..default()
+            },
+            // Keeps clicks from reaching the world or panels underneath
+            Interaction::default(),
+            PauseMenu,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(12.0)),
+                        min_size: Size::width(Val::Px(320.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.36, 0.33, 0.27).into(),
+                    ..default()
+                })
+                .with_children(|panel| match *screen {
+                    PauseScreen::Main => {
+                        panel.spawn(TextBundle::from_section("Paused", text_style.clone()));
+                        button(panel, "Resume", PauseMenuButton::Resume, false);
+                        button(panel, "Save", PauseMenuButton::Save, false);
+                        button(panel, "Load", PauseMenuButton::Load, false);
+                        button(panel, "Load autosave", PauseMenuButton::LoadAutosave, false);
+                        button(panel, "Settings", PauseMenuButton::Settings, false);
+                        button(panel, "Quit", PauseMenuButton::Quit, false);
+                    }
+                    PauseScreen::Settings(current) => {
+                        panel.spawn(TextBundle::from_section("Settings", text_style.clone()));
+                        panel.spawn(NodeBundle::default()).with_children(|tabs| {
+                            for tab in SettingsTab::ALL {
+                                button(tabs, tab.name(), PauseMenuButton::Tab(tab), tab == current);
+                            }
+                        });
+
+                        if current.settings().is_empty() && current.values().is_empty() {
+                            panel.spawn(TextBundle::from_section(
+                                "There are no sounds yet.",
+                                text_style.clone(),
+                            ));
+                        }
+                        for setting in current.settings() {
+                            let value = if settings.get(*setting) { "On" } else { "Off" };
+                            button(
+                                panel,
+                                &format!("{}: {}", setting.label(), value),
+                                PauseMenuButton::Toggle(*setting),
+                                false,
+                            );
+                        }
+                        for setting in current.values() {
+                            panel
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    ..default()
+                                })
+                                .with_children(|row| {
+                                    button(row, "-", PauseMenuButton::Step(*setting, -1), false);
+                                    button(row, "+", PauseMenuButton::Step(*setting, 1), false);
+                                    row.spawn(TextBundle::from_section(
+                                        format!(
+                                            "{}: {}",
+                                            setting.label(),
+                                            setting.format(settings.value(*setting))
+                                        ),
+                                        text_style.clone(),
+                                    ));
+                                });
+                        }
+
+                        button(panel, "Back", PauseMenuButton::Back, false);
+                    }
+                });
+        });
+}
@@ -0,0 +1,152 @@
+//! Named tracing spans for the heavier systems and an optional on-screen
+//! warning when one of them goes over its per-frame budget.
+//!
+//! Spans show up in any tracing subscriber (e.g. with `--features bevy/trace`).
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy::utils::tracing::span::EnteredSpan;
+use bevy::utils::HashMap;
+
+use crate::input::{Action, ActionInput};
+use crate::ui::UiFont;
+
+pub struct ProfilingPlugin;
+
+impl Plugin for ProfilingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FrameBudget>()
+            .init_resource::<SystemTimings>()
+            .add_startup_system(setup_budget_warning)
+            .add_system(toggle_frame_budget)
+            .add_system(report_frame_budget.in_base_set(CoreSet::Last));
+    }
+}
+
+#[derive(Resource)]
+pub struct FrameBudget {
+    /// Show a warning when an instrumented system exceeds `budget`.
+    pub enabled: bool,
+    /// Time a single instrumented system may take per frame.
+    pub budget: Duration,
+    /// How long a warning stays on screen.
+    pub warning_duration: Duration,
+}
+
+impl Default for FrameBudget {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+            budget: Duration::from_millis(2),
+            warning_duration: Duration::from_secs(3),
+        }
+    }
+}
+
+/// Time spent in each instrumented system during the current frame.
+#[derive(Resource, Default)]
+pub struct SystemTimings(Mutex<HashMap<&'static str, Duration>>);
+
+impl SystemTimings {
+    /// Opens a tracing span for `name` and records the elapsed time when the
+    /// returned scope is dropped.
+    pub fn scope(&self, name: &'static str) -> ProfileScope<'_> {
+        ProfileScope {
+            name,
+            start: Instant::now(),
+            timings: self,
+            _span: info_span!("system", name).entered(),
+        }
+    }
+}
+
+pub struct ProfileScope<'a> {
+    name: &'static str,
+    start: Instant,
+    timings: &'a SystemTimings,
+    _span: EnteredSpan,
+}
+
+impl Drop for ProfileScope<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        if let Ok(mut timings) = self.timings.0.lock() {
+            *timings.entry(self.name).or_default() += elapsed;
+        }
+    }
+}
+
+#[derive(Component)]
+struct BudgetWarningText {
+    timer: Timer,
+}
+
+fn setup_budget_warning(mut commands: Commands, font: Res<UiFont>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font.0.clone(),
+                font_size: 16.0,
+                color: Color::ORANGE_RED,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            ..default()
+        }),
+        BudgetWarningText {
+            timer: Timer::default(),
+        },
+    ));
+}
+
+fn toggle_frame_budget(actions: ActionInput, mut budget: ResMut<FrameBudget>) {
+    if actions.just_pressed(Action::ToggleFrameBudget) {
+        budget.enabled = !budget.enabled;
+    }
+}
+
+fn report_frame_budget(
+    time: Res<Time>,
+    budget: Res<FrameBudget>,
+    timings: Res<SystemTimings>,
+    mut warning: Query<(&mut Text, &mut BudgetWarningText)>,
+) {
+    let Ok(mut timings) = timings.0.lock() else {
+        return;
+    };
+    let (mut text, mut warning) = warning.single_mut();
+
+    if budget.enabled {
+        let slowest = timings
+            .iter()
+            .filter(|(_, elapsed)| **elapsed > budget.budget)
+            .max_by_key(|(_, elapsed)| **elapsed);
+
+        if let Some((name, elapsed)) = slowest {
+            let message = format!(
+                "{} took {:.2} ms (budget {:.2} ms)",
+                name,
+                elapsed.as_secs_f64() * 1000.0,
+                budget.budget.as_secs_f64() * 1000.0
+            );
+            warn!("{}", message);
+            text.sections[0].value = message;
+            warning.timer = Timer::new(budget.warning_duration, TimerMode::Once);
+        }
+    }
+    timings.clear();
+
+    warning.timer.tick(time.delta());
+    if !budget.enabled || warning.timer.just_finished() {
+        text.sections[0].value.clear();
+    }
+}
@@ -0,0 +1,163 @@
+//! Alternative "toon" look: flat unlit colors with dark inverted-hull outlines.
+//!
+//! Useful on low-contrast displays and for screenshots. Skinned meshes (the
+//! player models) are only flattened, since their vertices are posed by the
+//! skeleton and a scaled hull would not follow it.
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::render::mesh::skinning::SkinnedMesh;
+use bevy::render::render_resource::Face;
+use bevy::utils::HashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::input::{Action, ActionInput};
+use crate::minimap::MinimapViewOutline;
+use crate::profiling::SystemTimings;
+use crate::water::WaterSurface;
+use crate::{Gameplay, Ground};
+
+pub struct RenderModePlugin;
+
+impl Plugin for RenderModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RenderSettings>()
+            .init_resource::<FlattenedMaterials>()
+            .add_startup_system(setup_outline_material)
+            .add_system(toggle_toon_mode.in_set(Gameplay))
+            .add_system(flatten_materials.after(toggle_toon_mode))
+            .add_system(update_outlines.after(toggle_toon_mode));
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// Draw the scene with flat colors and outlines.
+    pub toon: bool,
+    /// How much larger than the mesh the outline hull is, as a fraction.
+    pub outline_width: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            toon: false,
+            outline_width: 0.06,
+        }
+    }
+}
+
+/// Materials that were lit before toon mode was switched on.
+#[derive(Resource, Default)]
+struct FlattenedMaterials(HashSet<HandleId>);
+
+#[derive(Resource)]
+struct OutlineMaterial(Handle<StandardMaterial>);
+
+/// Marks an entity that has an outline hull as a child.
+#[derive(Component)]
+struct Outlined;
+
+/// The back-face-only copy of a mesh that draws its outline.
+#[derive(Component)]
+struct OutlineHull;
+
+fn setup_outline_material(mut commands: Commands, mut materials: ResMut<Assets<StandardMaterial>>) {
+    let material = materials.add(StandardMaterial {
+        base_color: Color::BLACK,
+        unlit: true,
+        cull_mode: Some(Face::Front),
+        ..default()
+    });
+    commands.insert_resource(OutlineMaterial(material));
+}
+
+fn toggle_toon_mode(actions: ActionInput, mut settings: ResMut<RenderSettings>) {
+    if actions.just_pressed(Action::ToggleToonShading) {
+        settings.toon = !settings.toon;
+        info!("Toon render mode: {}", settings.toon);
+    }
+}
+
+/// Switches every lit material to unlit while toon mode is on, including
+/// materials loaded later, and restores them when it is switched off.
+fn flatten_materials(
+    settings: Res<RenderSettings>,
+    mut flattened: ResMut<FlattenedMaterials>,
+    mut material_events: EventReader<AssetEvent<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    timings: Res<SystemTimings>,
+) {
+    let _scope = timings.scope("flatten_materials");
+
+    let materials_added = material_events
+        .iter()
+        .any(|event| matches!(event, AssetEvent::Created { .. }));
+
+    if settings.toon && (settings.is_changed() || materials_added) {
+        let lit: Vec<HandleId> = materials
+            .iter()
+            .filter(|(_, material)| !material.unlit)
+            .map(|(id, _)| id)
+            .collect();
+        for id in lit {
+            if let Some(material) = materials.get_mut(&Handle::weak(id)) {
+                material.unlit = true;
+                flattened.0.insert(id);
+            }
+        }
+    } else if !settings.toon && settings.is_changed() {
+        for id in flattened.0.drain() {
+            if let Some(material) = materials.get_mut(&Handle::weak(id)) {
+                material.unlit = false;
+            }
+        }
+    }
+}
+
+fn update_outlines(
+    mut commands: Commands,
+    settings: Res<RenderSettings>,
+    outline_material: Res<OutlineMaterial>,
+    meshes: Query<
+        (Entity, &Handle<Mesh>),
+        (
+            Without<Outlined>,
+            Without<OutlineHull>,
+            Without<SkinnedMesh>,
+            Without<Ground>,
+            Without<WaterSurface>,
+            Without<MinimapViewOutline>,
+        ),
+    >,
+    outlined: Query<Entity, With<Outlined>>,
+    hulls: Query<Entity, With<OutlineHull>>,
+    timings: Res<SystemTimings>,
+) {
+    let _scope = timings.scope("update_outlines");
+
+    if settings.toon {
+        for (entity, mesh) in meshes.iter() {
+            let hull = commands
+                .spawn((
+                    PbrBundle {
+                        mesh: mesh.clone(),
+                        material: outline_material.0.clone(),
+                        transform: Transform::from_scale(Vec3::splat(1.0 + settings.outline_width)),
+                        ..default()
+                    },
+                    OutlineHull,
+                ))
+                .id();
+            commands.entity(entity).insert(Outlined).add_child(hull);
+        }
+    } else if settings.is_changed() {
+        for hull in hulls.iter() {
+            commands.entity(hull).despawn_recursive();
+        }
+        for entity in outlined.iter() {
+            commands.entity(entity).remove::<Outlined>();
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! Replays. Every game records the player's commands (selection changes,
+//! orders, construction sites placed and combat options toggled) with the
+//! tick they took effect on, and writes them to `replays/last.ron` on
+//! quitting. Start the game with `--replay <file>` to watch one back: the
+//! world is rebuilt from the same seed and the recorded commands are fed
+//! back into the event streams at the start of their ticks, while the
+//! player's own orders are dropped.
+//!
+//! A replay starts from a fresh game, so loading a save stops the
+//! recording.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::building::{
+    start_construction_sites, BuildingKind, ConstructionSite, PlaceConstructionSite,
+};
+use crate::chatbox::{GameMessage, MessageCategory};
+use crate::combat::{apply_combat_toggles, ToggleCombatOption};
+use crate::command_queue::{carry_out_orders, PlayerOrder, UnitCommand};
+use crate::grid::Footprint;
+use crate::map_file::map_path_from_args;
+use crate::rng::WorldSeed;
+use crate::save::LoadGame;
+use crate::{Gameplay, Selected, SelectionChanged, UnitType};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match replay_path_from_args() {
+            Some(path) => match ReplayFile::load(&path) {
+                Ok(replay) => {
+                    if replay.map != map_path_from_args() {
+                        warn!("The replay was recorded on a different map");
+                    }
+                    app.insert_resource(WorldSeed(replay.seed))
+                        .insert_resource(Playback { replay, next: 0 });
+                }
+                Err(error) => error!("Can't load replay {}: {}", path.display(), error),
+            },
+            None => {
+                let seed = app.world.get_resource_or_insert_with(WorldSeed::default).0;
+                app.insert_resource(Recording(ReplayFile {
+                    seed,
+                    map: map_path_from_args(),
+                    commands: Vec::new(),
+                }));
+            }
+        }
+
+        app.init_resource::<ReplayTick>()
+            .add_startup_system(number_units.in_base_set(StartupSet::PostStartup))
+            .add_system(
+                record_commands
+                    .in_base_set(CoreSet::PostUpdate)
+                    .in_set(Gameplay)
+                    .run_if(resource_exists::<Recording>()),
+            )
+            .add_system(
+                drop_player_commands
+                    .before(carry_out_orders)
+                    .before(start_construction_sites)
+                    .before(apply_combat_toggles)
+                    .in_base_set(CoreSet::PostUpdate)
+                    .run_if(resource_exists::<Playback>()),
+            )
+            // Recorded commands are carried out in the tick they were
+            // recorded for, rather than after the frame's ticks
+            .add_systems(
+                (
+                    play_commands,
+                    carry_out_orders.after(play_commands),
+                    start_construction_sites.after(play_commands),
+                    apply_combat_toggles.after(play_commands),
+                )
+                    .before(count_ticks)
+                    .in_set(Gameplay)
+                    .distributive_run_if(resource_exists::<Playback>())
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                count_ticks
+                    .in_set(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                stop_recording_on_load
+                    .run_if(resource_exists::<Recording>())
+                    .run_if(on_event::<LoadGame>()),
+            )
+            .add_system(
+                save_recording_on_exit
+                    .in_base_set(CoreSet::Last)
+                    .run_if(resource_exists::<Recording>()),
+            );
+    }
+}
+
+/// Where the replay of the last game is written.
+const REPLAY_PATH: &str = "replays/last.ron";
+
+/// Number a unit goes by in replays, the same every time the same world is
+/// started.
+#[derive(Component)]
+struct ReplayId(u32);
+
+/// Ticks of play so far, not counting time paused.
+#[derive(Resource, Default)]
+struct ReplayTick(u64);
+
+/// The game being recorded.
+#[derive(Resource)]
+struct Recording(ReplayFile);
+
+/// The replay being watched and the index of the next command to play.
+#[derive(Resource)]
+struct Playback {
+    replay: ReplayFile,
+    next: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReplayFile {
+    /// Seed the world was generated from.
+    seed: u64,
+    /// Map file the game was started with, if any.
+    map: Option<PathBuf>,
+    commands: Vec<RecordedCommand>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedCommand {
+    /// Tick the command took effect on.
+    tick: u64,
+    command: ReplayCommand,
+}
+
+/// A player command, with units given by their `ReplayId` and construction
+/// sites by their corner tile.
+#[derive(Serialize, Deserialize)]
+enum ReplayCommand {
+    /// The units selected after a selection change.
+    Select(Vec<u32>),
+    Order {
+        units: Vec<u32>,
+        order: ReplayOrder,
+        queued: bool,
+    },
+    Stop(Vec<u32>),
+    PlaceSite {
+        kind: BuildingKind,
+        tile: (i32, i32),
+        builders: Vec<u32>,
+    },
+    ToggleAttackStyle(Vec<u32>),
+    ToggleAutoRetaliate(Vec<u32>),
+}
+
+#[derive(Serialize, Deserialize)]
+enum ReplayOrder {
+    Move((f32, f32, f32)),
+    Attack(u32),
+    Build((i32, i32)),
+}
+
+#[derive(Debug)]
+enum ReplayError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "couldn't access the replay file: {}", error),
+            ReplayError::Parse(error) => write!(f, "couldn't parse the replay file: {}", error),
+            ReplayError::Write(error) => write!(f, "couldn't write the replay file: {}", error),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(error: std::io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for ReplayError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        ReplayError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for ReplayError {
+    fn from(error: ron::Error) -> Self {
+        ReplayError::Write(error)
+    }
+}
+
+impl ReplayFile {
+    fn load(path: &Path) -> Result<Self, ReplayError> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        // One line per command
+        let config = PrettyConfig::new().depth_limit(2);
+        let text = ron::ser::to_string_pretty(self, config)?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// The replay passed with `--replay`, if any.
+fn replay_path_from_args() -> Option<PathBuf> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--replay");
+    args.next()?;
+    args.next().map(PathBuf::from)
+}
+
+/// Numbers the starting units by kind and position, which a world started
+/// from the same seed always repeats.
+fn number_units(mut commands: Commands, units: Query<(Entity, &UnitType, &Transform)>) {
+    let mut units: Vec<_> = units.iter().collect();
+    units.sort_by(|(_, kind_a, a), (_, kind_b, b)| {
+        (**kind_a as u8)
+            .cmp(&(**kind_b as u8))
+            .then(a.translation.x.total_cmp(&b.translation.x))
+            .then(a.translation.z.total_cmp(&b.translation.z))
+    });
+    for (id, (entity, ..)) in units.into_iter().enumerate() {
+        commands.entity(entity).insert(ReplayId(id as u32));
+    }
+}
+
+fn count_ticks(mut tick: ResMut<ReplayTick>) {
+    tick.0 += 1;
+}
+
+/// Writes down the commands the player gave this frame, which are carried
+/// out before the next tick.
+fn record_commands(
+    mut recording: ResMut<Recording>,
+    tick: Res<ReplayTick>,
+    mut orders: EventReader<PlayerOrder>,
+    mut placements: EventReader<PlaceConstructionSite>,
+    mut toggles: EventReader<ToggleCombatOption>,
+    mut selection_changed: EventReader<SelectionChanged>,
+    ids: Query<&ReplayId>,
+    selected: Query<&ReplayId, With<Selected>>,
+    sites: Query<&Footprint, With<ConstructionSite>>,
+) {
+    // Units from a loaded save have no number and are left out
+    let ids_of = |units: &[Entity]| -> Vec<u32> {
+        units
+            .iter()
+            .filter_map(|unit| ids.get(*unit).ok())
+            .map(|id| id.0)
+            .collect()
+    };
+
+    let mut commands = Vec::new();
+    if selection_changed.iter().last().is_some() {
+        commands.push(ReplayCommand::Select(
+            selected.iter().map(|id| id.0).collect(),
+        ));
+    }
+    for order in orders.iter() {
+        commands.push(match order {
+            PlayerOrder::Command {
+                units,
+                command,
+                queued,
+            } => {
+                let order = match *command {
+                    UnitCommand::Move(point) => ReplayOrder::Move(point.into()),
+                    UnitCommand::Attack(target) => match ids.get(target) {
+                        Ok(id) => ReplayOrder::Attack(id.0),
+                        Err(_) => continue,
+                    },
+                    UnitCommand::Build(site) => match sites.get(site) {
+                        Ok(footprint) => ReplayOrder::Build(footprint.min.into()),
+                        Err(_) => continue,
+                    },
+                };
+                ReplayCommand::Order {
+                    units: ids_of(units),
+                    order,
+                    queued: *queued,
+                }
+            }
+            PlayerOrder::Stop(units) => ReplayCommand::Stop(ids_of(units)),
+        });
+    }
+    for placement in placements.iter() {
+        commands.push(ReplayCommand::PlaceSite {
+            kind: placement.kind,
+            tile: placement.footprint.min.into(),
+            builders: ids_of(&placement.builders),
+        });
+    }
+    for toggle in toggles.iter() {
+        commands.push(match toggle {
+            ToggleCombatOption::AttackStyle(units) => {
+                ReplayCommand::ToggleAttackStyle(ids_of(units))
+            }
+            ToggleCombatOption::AutoRetaliate(units) => {
+                ReplayCommand::ToggleAutoRetaliate(ids_of(units))
+            }
+        });
+    }
+
+    recording
+        .0
+        .commands
+        .extend(commands.into_iter().map(|command| RecordedCommand {
+            tick: tick.0,
+            command,
+        }));
+}
+
+fn drop_player_commands(
+    mut orders: ResMut<Events<PlayerOrder>>,
+    mut placements: ResMut<Events<PlaceConstructionSite>>,
+    mut toggles: ResMut<Events<ToggleCombatOption>>,
+) {
+    orders.clear();
+    placements.clear();
+    toggles.clear();
+}
+
+/// Sends the recorded commands due this tick.
+fn play_commands(
+    mut commands: Commands,
+    mut playback: ResMut<Playback>,
+    tick: Res<ReplayTick>,
+    mut orders: EventWriter<PlayerOrder>,
+    mut placements: EventWriter<PlaceConstructionSite>,
+    mut toggles: EventWriter<ToggleCombatOption>,
+    mut selection_changed: EventWriter<SelectionChanged>,
+    mut messages: EventWriter<GameMessage>,
+    units: Query<(Entity, &ReplayId)>,
+    selected: Query<Entity, With<Selected>>,
+    sites: Query<(Entity, &Footprint), With<ConstructionSite>>,
+) {
+    let entities: HashMap<u32, Entity> = units.iter().map(|(entity, id)| (id.0, entity)).collect();
+    let entities_of = |ids: &[u32]| -> Vec<Entity> {
+        ids.iter()
+            .filter_map(|id| entities.get(id).copied())
+            .collect()
+    };
+
+    let Playback { replay, next } = playback.as_mut();
+    let playing = *next < replay.commands.len();
+    while let Some(recorded) = replay.commands.get(*next) {
+        if recorded.tick > tick.0 {
+            break;
+        }
+        *next += 1;
+
+        match &recorded.command {
+            ReplayCommand::Select(ids) => {
+                for entity in selected.iter() {
+                    commands.entity(entity).remove::<Selected>();
+                }
+                for entity in entities_of(ids) {
+                    commands.entity(entity).insert(Selected {});
+                }
+                selection_changed.send(SelectionChanged);
+            }
+            ReplayCommand::Order {
+                units,
+                order,
+                queued,
+            } => {
+                let command = match order {
+                    ReplayOrder::Move(point) => UnitCommand::Move((*point).into()),
+                    ReplayOrder::Attack(target) => match entities.get(target) {
+                        Some(target) => UnitCommand::Attack(*target),
+                        None => continue,
+                    },
+                    ReplayOrder::Build(tile) => {
+                        let tile = IVec2::from(*tile);
+                        match sites.iter().find(|(_, footprint)| footprint.min == tile) {
+                            Some((site, _)) => UnitCommand::Build(site),
+                            None => continue,
+                        }
+                    }
+                };
+                orders.send(PlayerOrder::Command {
+                    units: entities_of(units),
+                    command,
+                    queued: *queued,
+                });
+            }
+            ReplayCommand::Stop(units) => orders.send(PlayerOrder::Stop(entities_of(units))),
+            ReplayCommand::PlaceSite {
+                kind,
+                tile,
+                builders,
+            } => placements.send(PlaceConstructionSite {
+                kind: *kind,
+                footprint: Footprint {
+                    min: (*tile).into(),
+                    size: kind.footprint_size(),
+                },
+                builders: entities_of(builders),
+            }),
+            ReplayCommand::ToggleAttackStyle(units) => {
+                toggles.send(ToggleCombatOption::AttackStyle(entities_of(units)))
+            }
+            ReplayCommand::ToggleAutoRetaliate(units) => {
+                toggles.send(ToggleCombatOption::AutoRetaliate(entities_of(units)))
+            }
+        }
+    }
+
+    if playing && *next == replay.commands.len() {
+        messages.send(GameMessage::new(
+            MessageCategory::Game,
+            "The replay has finished.",
+        ));
+    }
+}
+
+/// A loaded save can't be replayed from the start of the game, so the
+/// recording ends where the save was loaded.
+fn stop_recording_on_load(mut commands: Commands, recording: Res<Recording>) {
+    save_recording(&recording.0);
+    commands.remove_resource::<Recording>();
+}
+
+fn save_recording_on_exit(exits: EventReader<AppExit>, recording: Res<Recording>) {
+    if !exits.is_empty() {
+        save_recording(&recording.0);
+    }
+}
+
+fn save_recording(replay: &ReplayFile) {
+    let path = Path::new(REPLAY_PATH);
+    match replay.save(path) {
+        Ok(()) => info!("Saved the replay to {}", path.display()),
+        Err(error) => error!("Can't save the replay to {}: {}", path.display(), error),
+    }
+}
@@ -0,0 +1,71 @@
+//! The game's random numbers. Everything random that affects the world, from
+//! the generated map to combat rolls, draws from `GameRng`, which is seeded
+//! from `WorldSeed`. Start the game with `--seed <number>` to play the same
+//! world again; otherwise a fresh seed is picked and logged. Saves keep the
+//! generator's whole state, so a loaded game carries on exactly where it
+//! was saved.
+
+use bevy::prelude::*;
+use rand::{Error, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+/// The seed the game's random numbers started from.
+#[derive(Resource, Clone, Copy)]
+pub struct WorldSeed(pub u64);
+
+impl Default for WorldSeed {
+    fn default() -> Self {
+        let seed = seed_from_args().unwrap_or_else(|| rand::thread_rng().gen());
+        info!("World seed: {}", seed);
+        Self(seed)
+    }
+}
+
+/// Random numbers for everything that affects the world.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct GameRng(ChaCha12Rng);
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self(ChaCha12Rng::seed_from_u64(seed))
+    }
+}
+
+impl FromWorld for GameRng {
+    fn from_world(world: &mut World) -> Self {
+        Self::new(world.get_resource_or_insert_with(WorldSeed::default).0)
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+/// The seed passed with `--seed`, if any.
+fn seed_from_args() -> Option<u64> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--seed");
+    args.next()?;
+    let arg = args.next()?;
+    match arg.parse() {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            error!("Seed '{}' isn't a whole number, using a random one", arg);
+            None
+        }
+    }
+}
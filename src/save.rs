@@ -0,0 +1,516 @@
+//! Saving and loading games. F5 quicksaves and F9 quickloads, and the
+//! Save and Load buttons in the pause menu do the same. Saves are written
+//! in the background so the game doesn't stall. A save holds the
+//! map with its finished structures, construction sites, units and the
+//! camera, plus the state of the random numbers, so a game carries on the
+//! same way every time its save is loaded. Saving leaves the running game
+//! untouched.
+//!
+//! Units come back idle: orders, queues and control groups aren't saved.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::tasks::{IoTaskPool, Task};
+use futures_lite::future;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::building::{
+    spawn_building, spawn_construction_site, BuildAssets, Building, BuildingKind, ConstructionSite,
+};
+use crate::camera::MainCamera;
+use crate::chatbox::{GameMessage, MessageCategory};
+use crate::combat::{AttackStyle, AutoRetaliate, RangedWeapon};
+use crate::grid::{Footprint, OccupancyGrid};
+use crate::health::Health;
+use crate::input::{Action, ActionInput};
+use crate::map_file::{MapBuilding, MapData, MapError};
+use crate::npc::{spawn_goblin, GoblinAssets, NpcSpawnConfig, Wander};
+use crate::rng::GameRng;
+use crate::terrain::TileMap;
+use crate::{spawn_worker, Gameplay, PlayerName, SelectionChanged, UnitType};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingSaves>()
+            .add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            .add_system(quicksave_and_quickload.in_set(Gameplay))
+            .add_system(
+                save_game
+                    .after(quicksave_and_quickload)
+                    .run_if(on_event::<SaveGame>()),
+            )
+            .add_system(finish_saves.after(save_game))
+            .add_system(load_game.after(save_game).run_if(on_event::<LoadGame>()));
+    }
+}
+
+const SAVE_DIRECTORY: &str = "saves";
+
+/// A save file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveSlot {
+    Quicksave,
+    /// One of the rotating autosave files, counting from 0.
+    Autosave(usize),
+}
+
+impl SaveSlot {
+    pub fn path(&self) -> PathBuf {
+        let name = match self {
+            SaveSlot::Quicksave => "quicksave".to_string(),
+            SaveSlot::Autosave(index) => format!("autosave_{}", index + 1),
+        };
+        Path::new(SAVE_DIRECTORY).join(format!("{}.ron", name))
+    }
+}
+
+/// Saves the game to a save file.
+pub struct SaveGame(pub SaveSlot);
+
+/// Replaces the world with the one in a save file.
+pub struct LoadGame(pub SaveSlot);
+
+/// Saves still being written.
+#[derive(Resource, Default)]
+struct PendingSaves(Vec<(SaveSlot, Task<Result<(), SaveError>>)>);
+
+#[derive(Serialize, Deserialize)]
+struct SaveData {
+    /// The game's random numbers, to carry on from.
+    rng: GameRng,
+    /// The tiles, finished structures and player starts.
+    map: MapData,
+    construction_sites: Vec<SavedSite>,
+    units: Vec<SavedUnit>,
+    camera: SavedCamera,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedSite {
+    kind: BuildingKind,
+    /// Tile under the site's corner with the lowest coordinates.
+    tile: (i32, i32),
+    progress: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedUnit {
+    kind: UnitType,
+    #[serde(default)]
+    name: Option<String>,
+    position: (f32, f32, f32),
+    /// Turn about the vertical axis, in radians.
+    facing: f32,
+    health: u32,
+    max_health: u32,
+    #[serde(default)]
+    ranged: bool,
+    #[serde(default)]
+    auto_retaliate: Option<bool>,
+    /// Point an NPC wanders around and guards.
+    #[serde(default)]
+    home: Option<(f32, f32, f32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedCamera {
+    position: (f32, f32, f32),
+    rotation: (f32, f32, f32, f32),
+    zoom: f32,
+}
+
+#[derive(Debug)]
+enum SaveError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+    Map(MapError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "couldn't access the save file: {}", error),
+            SaveError::Parse(error) => write!(f, "couldn't parse the save file: {}", error),
+            SaveError::Write(error) => write!(f, "couldn't write the save file: {}", error),
+            SaveError::Map(error) => write!(f, "the saved map is broken: {}", error),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(error: std::io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        SaveError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(error: ron::Error) -> Self {
+        SaveError::Write(error)
+    }
+}
+
+impl From<MapError> for SaveError {
+    fn from(error: MapError) -> Self {
+        SaveError::Map(error)
+    }
+}
+
+impl SaveData {
+    fn load(path: &Path) -> Result<Self, SaveError> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), SaveError> {
+        // Deep enough for one line per unit and row of tiles
+        let config = PrettyConfig::new().depth_limit(3);
+        let text = ron::ser::to_string_pretty(self, config)?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        // Write a new file and swap it in, so a save is never left half
+        // written
+        let temporary = path.with_extension("ron.tmp");
+        std::fs::write(&temporary, text)?;
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
+
+fn quicksave_and_quickload(
+    actions: ActionInput,
+    mut saves: EventWriter<SaveGame>,
+    mut loads: EventWriter<LoadGame>,
+) {
+    if actions.just_pressed(Action::QuickSave) {
+        saves.send(SaveGame(SaveSlot::Quicksave));
+    }
+    if actions.just_pressed(Action::QuickLoad) {
+        loads.send(LoadGame(SaveSlot::Quicksave));
+    }
+}
+
+/// Takes a snapshot of the game and writes it in the background.
+fn save_game(
+    mut saves: EventReader<SaveGame>,
+    mut pending: ResMut<PendingSaves>,
+    tile_map: Res<TileMap>,
+    map: Res<MapData>,
+    rng: Res<GameRng>,
+    buildings: Query<(&Building, &Footprint)>,
+    sites: Query<(&ConstructionSite, &Footprint)>,
+    units: Query<(
+        &UnitType,
+        &Transform,
+        &Health,
+        Option<&PlayerName>,
+        Option<&AttackStyle>,
+        Option<&AutoRetaliate>,
+        Option<&Wander>,
+    )>,
+    camera: Query<(&Transform, &Projection), With<MainCamera>>,
+) {
+    let Some(SaveGame(slot)) = saves.iter().last() else {
+        return;
+    };
+    let Ok((camera_transform, projection)) = camera.get_single() else {
+        return;
+    };
+
+    let buildings = buildings
+        .iter()
+        .map(|(building, footprint)| MapBuilding {
+            kind: building.kind,
+            tile: footprint.min.into(),
+        })
+        .collect();
+    let construction_sites = sites
+        .iter()
+        .map(|(site, footprint)| SavedSite {
+            kind: site.kind,
+            tile: footprint.min.into(),
+            progress: site.progress,
+        })
+        .collect();
+    let units = units
+        .iter()
+        .map(
+            |(kind, transform, health, name, style, auto_retaliate, wander)| SavedUnit {
+                kind: *kind,
+                name: name.map(|name| name.0.clone()),
+                position: transform.translation.into(),
+                facing: transform.rotation.to_euler(EulerRot::YXZ).0,
+                health: health.current,
+                max_health: health.max,
+                ranged: matches!(style, Some(AttackStyle::Ranged(_))),
+                auto_retaliate: auto_retaliate.map(|auto_retaliate| auto_retaliate.0),
+                home: wander.map(|wander| wander.home.into()),
+            },
+        )
+        .collect();
+    let zoom = match projection {
+        Projection::Orthographic(projection) => projection.scale,
+        Projection::Perspective(_) => 1.0,
+    };
+
+    let data = SaveData {
+        rng: rng.clone(),
+        map: MapData::from_tile_map(&tile_map, buildings, map.player_starts.clone()),
+        construction_sites,
+        units,
+        camera: SavedCamera {
+            position: camera_transform.translation.into(),
+            rotation: camera_transform.rotation.into(),
+            zoom,
+        },
+    };
+
+    let path = slot.path();
+    let task = IoTaskPool::get().spawn(async move { data.save(&path) });
+    pending.0.push((*slot, task));
+}
+
+/// Reports on saves that have finished writing.
+fn finish_saves(mut pending: ResMut<PendingSaves>, mut messages: EventWriter<GameMessage>) {
+    pending.0.retain_mut(|(slot, task)| {
+        let Some(result) = future::block_on(future::poll_once(task)) else {
+            return true;
+        };
+        match (result, *slot) {
+            (Ok(()), SaveSlot::Quicksave) => {
+                messages.send(GameMessage::new(MessageCategory::Game, "Game saved."))
+            }
+            (Ok(()), SaveSlot::Autosave(_)) => {
+                messages.send(GameMessage::new(MessageCategory::Game, "Game autosaved."))
+            }
+            (Err(error), _) => {
+                error!(
+                    "Can't save the game to {}: {}",
+                    slot.path().display(),
+                    error
+                );
+                messages.send(GameMessage::new(
+                    MessageCategory::Error,
+                    "The game couldn't be saved.",
+                ));
+            }
+        }
+        false
+    });
+}
+
+fn load_game(
+    mut loads: EventReader<LoadGame>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    build_assets: Res<BuildAssets>,
+    goblin_assets: Res<GoblinAssets>,
+    npc_config: Res<NpcSpawnConfig>,
+    mut tile_map: ResMut<TileMap>,
+    mut map: ResMut<MapData>,
+    mut occupancy: ResMut<OccupancyGrid>,
+    mut rng: ResMut<GameRng>,
+    structures: Query<(Entity, &Footprint), Or<(With<Building>, With<ConstructionSite>)>>,
+    units: Query<Entity, With<UnitType>>,
+    mut camera: Query<(&mut Transform, &mut Projection), With<MainCamera>>,
+    mut messages: EventWriter<GameMessage>,
+    mut selection_changed: EventWriter<SelectionChanged>,
+) {
+    let Some(LoadGame(slot)) = loads.iter().last() else {
+        return;
+    };
+    let (new_tile_map, data) =
+        match SaveData::load(&slot.path()).and_then(|data| Ok((data.map.tile_map()?, data))) {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                error!("Can't load {}: {}", slot.path().display(), error);
+                messages.send(GameMessage::new(
+                    MessageCategory::Error,
+                    "There is no saved game to load.",
+                ));
+                return;
+            }
+        };
+
+    // Clear out the current world. Doodads and fishing spots stay, apart
+    // from those on tiles the saved map changes.
+    for (entity, footprint) in structures.iter() {
+        occupancy.free(footprint.tiles());
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in units.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    *tile_map = new_tile_map;
+    *rng = data.rng;
+
+    for building in data.map.buildings.iter() {
+        let footprint = Footprint {
+            min: building.tile.into(),
+            size: building.kind.footprint_size(),
+        };
+        spawn_building(
+            &mut commands,
+            &build_assets,
+            &tile_map,
+            &mut occupancy,
+            building.kind,
+            footprint,
+        );
+    }
+    for site in data.construction_sites.iter() {
+        let footprint = Footprint {
+            min: site.tile.into(),
+            size: site.kind.footprint_size(),
+        };
+        spawn_construction_site(
+            &mut commands,
+            &build_assets,
+            &tile_map,
+            &mut occupancy,
+            site.kind,
+            footprint,
+            site.progress,
+        );
+    }
+
+    for unit in data.units.iter() {
+        let position = Vec3::from(unit.position);
+        let entity = match unit.kind {
+            UnitType::Worker => {
+                let name = unit.name.clone().unwrap_or_else(|| "Player".to_string());
+                spawn_worker(&mut commands, &asset_server, name, position)
+            }
+            UnitType::Goblin => {
+                let home = unit.home.map_or(position, Vec3::from);
+                spawn_goblin(&mut commands, &goblin_assets, &npc_config, home, position)
+            }
+        };
+
+        let mut entity = commands.entity(entity);
+        entity.insert(Health {
+            current: unit.health,
+            max: unit.max_health,
+        });
+        if unit.ranged {
+            entity.insert(AttackStyle::Ranged(RangedWeapon::SHORTBOW));
+        }
+        if let Some(auto_retaliate) = unit.auto_retaliate {
+            entity.insert(AutoRetaliate(auto_retaliate));
+        }
+        entity.add(rotate(unit.facing));
+    }
+
+    for (mut transform, mut projection) in camera.iter_mut() {
+        transform.translation = data.camera.position.into();
+        let (x, y, z, w) = data.camera.rotation;
+        transform.rotation = Quat::from_xyzw(x, y, z, w);
+        if let Projection::Orthographic(projection) = projection.as_mut() {
+            projection.scale = data.camera.zoom;
+        }
+    }
+
+    *map = data.map;
+    selection_changed.send(SelectionChanged);
+    messages.send(GameMessage::new(MessageCategory::Game, "Game loaded."));
+}
+
+/// Command turning a freshly spawned unit to face the way it was saved
+/// facing, keeping the rest of its transform.
+fn rotate(facing: f32) -> impl FnOnce(Entity, &mut World) {
+    move |entity, world| {
+        if let Some(mut transform) = world.get_mut::<Transform>(entity) {
+            transform.rotation = Quat::from_rotation_y(facing);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+
+    use super::*;
+
+    #[test]
+    fn save_data_round_trip() {
+        let mut rng = GameRng::new(7);
+        // Part way through the stream, as in a game being played
+        for _ in 0..5 {
+            rng.next_u32();
+        }
+        let data = SaveData {
+            rng: rng.clone(),
+            map: MapData {
+                origin: (-1, -1),
+                tiles: vec!["..".to_string(), "~T".to_string()],
+                heights: vec![vec![0.0, 0.5, 1.0]; 3],
+                buildings: vec![MapBuilding {
+                    kind: BuildingKind::House,
+                    tile: (-1, -1),
+                }],
+                player_starts: vec![(0.5, 0.5)],
+            },
+            construction_sites: vec![SavedSite {
+                kind: BuildingKind::Wall,
+                tile: (0, -1),
+                progress: 0.25,
+            }],
+            units: vec![SavedUnit {
+                kind: UnitType::Worker,
+                name: Some("Zezima".to_string()),
+                position: (0.5, 0.1, 0.5),
+                facing: 1.5,
+                health: 7,
+                max_health: 10,
+                ranged: true,
+                auto_retaliate: Some(false),
+                home: None,
+            }],
+            camera: SavedCamera {
+                position: (5.0, 10.0, 5.0),
+                rotation: (0.0, 0.38, 0.0, 0.92),
+                zoom: 0.5,
+            },
+        };
+
+        let path = std::env::temp_dir().join("osrssg_save_round_trip.ron");
+        data.save(&path).unwrap();
+        let mut loaded = SaveData::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        // The loaded game draws the same numbers the saved one would have
+        for _ in 0..3 {
+            assert_eq!(loaded.rng.next_u64(), rng.next_u64());
+        }
+        assert_eq!(loaded.map.tiles, data.map.tiles);
+        assert_eq!(loaded.map.heights, data.map.heights);
+        assert_eq!(loaded.map.buildings.len(), 1);
+        assert_eq!(loaded.map.buildings[0].tile, (-1, -1));
+        assert_eq!(loaded.construction_sites[0].kind, BuildingKind::Wall);
+        assert_eq!(loaded.construction_sites[0].progress, 0.25);
+
+        let unit = &loaded.units[0];
+        assert_eq!(unit.kind, UnitType::Worker);
+        assert_eq!(unit.name.as_deref(), Some("Zezima"));
+        assert_eq!(unit.position, (0.5, 0.1, 0.5));
+        assert_eq!((unit.health, unit.max_health), (7, 10));
+        assert!(unit.ranged);
+        assert_eq!(unit.auto_retaliate, Some(false));
+        assert_eq!(unit.home, None);
+        assert_eq!(loaded.camera.rotation, (0.0, 0.38, 0.0, 0.92));
+        assert_eq!(loaded.camera.zoom, 0.5);
+    }
+}
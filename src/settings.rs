@@ -0,0 +1,156 @@
+//! The settings file. Camera, key binding, display and gameplay settings,
+//! and the camera bookmarks, are read from `settings.ron` in the platform's
+//! config directory at startup and written back when the game quits, so
+//! changes made in the pause menu stick. Settings missing from the file, or
+//! the whole file, fall back to the defaults.
+
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::autosave::AutosaveConfig;
+use crate::camera::{CameraBookmarks, CameraSettings};
+use crate::combat::CombatSettings;
+use crate::context_menu::ContextMenuSettings;
+use crate::input::InputBindings;
+use crate::minimap::MinimapSettings;
+use crate::movement::MovementConfig;
+use crate::render_mode::RenderSettings;
+use crate::status_plates::StatusPlateSettings;
+
+pub struct SettingsFilePlugin;
+
+impl Plugin for SettingsFilePlugin {
+    fn build(&self, app: &mut App) {
+        // Before the startup systems that spawn the camera and panels
+        app.add_startup_system(load_settings.in_base_set(StartupSet::PreStartup))
+            .add_system(save_settings_on_exit.in_base_set(CoreSet::Last));
+    }
+}
+
+/// Name of the game's folder in the config directory.
+const CONFIG_FOLDER: &str = "osrssg";
+
+/// The settings resources kept in the settings file.
+#[derive(SystemParam)]
+struct StoredSettings<'w> {
+    camera: ResMut<'w, CameraSettings>,
+    bookmarks: ResMut<'w, CameraBookmarks>,
+    key_bindings: ResMut<'w, InputBindings>,
+    render: ResMut<'w, RenderSettings>,
+    status_plates: ResMut<'w, StatusPlateSettings>,
+    minimap: ResMut<'w, MinimapSettings>,
+    context_menu: ResMut<'w, ContextMenuSettings>,
+    combat: ResMut<'w, CombatSettings>,
+    movement: ResMut<'w, MovementConfig>,
+    autosave: ResMut<'w, AutosaveConfig>,
+}
+
+/// What's written to the settings file. Anything missing from the file is
+/// read as its default.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SettingsFile {
+    camera: CameraSettings,
+    bookmarks: CameraBookmarks,
+    /// Keys for the actions listed. Actions left out keep their default
+    /// keys, so ones added in later versions still get bound.
+    key_bindings: InputBindings,
+    render: RenderSettings,
+    status_plates: StatusPlateSettings,
+    minimap: MinimapSettings,
+    context_menu: ContextMenuSettings,
+    combat: CombatSettings,
+    movement: MovementConfig,
+    autosave: AutosaveConfig,
+}
+
+/// Where the settings file is kept: the platform's config directory, or
+/// the working directory if there doesn't seem to be one.
+fn settings_path() -> PathBuf {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let config_directory = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".config")))
+    };
+    config_directory
+        .map(|directory| directory.join(CONFIG_FOLDER))
+        .unwrap_or_default()
+        .join("settings.ron")
+}
+
+fn load_settings(mut settings: StoredSettings) {
+    let path = settings_path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        // First run, so the defaults it is
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return,
+        Err(error) => {
+            error!("Can't read settings from {}: {}", path.display(), error);
+            return;
+        }
+    };
+    let file: SettingsFile = match ron::from_str(&text) {
+        Ok(file) => file,
+        Err(error) => {
+            error!("Can't parse settings from {}: {}", path.display(), error);
+            return;
+        }
+    };
+
+    // Easing is a function, so it isn't in the file
+    *settings.camera = CameraSettings {
+        transition_easing: settings.camera.transition_easing,
+        ..file.camera
+    };
+    *settings.bookmarks = file.bookmarks;
+    settings.key_bindings.0.extend(file.key_bindings.0);
+    *settings.render = file.render;
+    *settings.status_plates = file.status_plates;
+    *settings.minimap = file.minimap;
+    *settings.context_menu = file.context_menu;
+    *settings.combat = file.combat;
+    *settings.movement = file.movement;
+    *settings.autosave = file.autosave.validated();
+    info!("Loaded settings from {}", path.display());
+}
+
+fn save_settings_on_exit(exits: EventReader<AppExit>, settings: StoredSettings) {
+    if exits.is_empty() {
+        return;
+    }
+
+    let file = SettingsFile {
+        camera: settings.camera.clone(),
+        bookmarks: settings.bookmarks.clone(),
+        key_bindings: settings.key_bindings.clone(),
+        render: settings.render.clone(),
+        status_plates: settings.status_plates.clone(),
+        minimap: settings.minimap.clone(),
+        context_menu: settings.context_menu.clone(),
+        combat: settings.combat.clone(),
+        movement: settings.movement.clone(),
+        autosave: settings.autosave.clone(),
+    };
+    let path = settings_path();
+    let result = ron::ser::to_string_pretty(&file, PrettyConfig::new())
+        .map_err(|error| error.to_string())
+        .and_then(|text| {
+            if let Some(directory) = path.parent() {
+                std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+            }
+            std::fs::write(&path, text).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        error!("Can't save settings to {}: {}", path.display(), error);
+    }
+}
@@ -0,0 +1,163 @@
+//! Small plates above every unit showing what it is and an icon for what it
+//! is doing. Plates hide when the camera is zoomed far out, and Alt turns
+//! them off altogether.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::building::BuildTask;
+use crate::camera::MainCamera;
+use crate::combat::AttackTask;
+use crate::input::{Action, ActionInput};
+use crate::ui::{UiFont, WorldAnchor};
+use crate::{Gameplay, Moving, UnitType};
+
+pub struct StatusPlatesPlugin;
+
+impl Plugin for StatusPlatesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StatusPlateSettings>()
+            .add_system(toggle_status_plates.in_set(Gameplay))
+            .add_system(spawn_status_plates)
+            .add_system(
+                update_status_plates
+                    .after(toggle_status_plates)
+                    .after(spawn_status_plates),
+            );
+    }
+}
+
+/// How far above the unit's origin the plate is drawn, above the health bar
+/// and focus marker.
+const STATUS_PLATE_OFFSET: Vec3 = Vec3::new(0.0, 1.9, 0.0);
+const STATUS_PLATE_FONT_SIZE: f32 = 12.0;
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusPlateSettings {
+    pub visible: bool,
+    /// Camera zoom beyond which plates are hidden to reduce clutter.
+    pub max_zoom: f32,
+}
+
+impl Default for StatusPlateSettings {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            max_zoom: 7.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum UnitActivity {
+    Idle,
+    Walking,
+    Attacking,
+    Building,
+}
+
+impl UnitActivity {
+    fn icon(&self) -> &'static str {
+        match self {
+            UnitActivity::Idle => "…",
+            UnitActivity::Walking => "➜",
+            UnitActivity::Attacking => "⚔",
+            UnitActivity::Building => "⚒",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            UnitActivity::Idle => Color::GRAY,
+            UnitActivity::Walking => Color::WHITE,
+            UnitActivity::Attacking => Color::rgb(1.0, 0.4, 0.3),
+            UnitActivity::Building => Color::rgb(1.0, 0.85, 0.3),
+        }
+    }
+}
+
+#[derive(Component)]
+struct StatusPlate {
+    owner: Entity,
+}
+
+fn toggle_status_plates(actions: ActionInput, mut settings: ResMut<StatusPlateSettings>) {
+    if actions.just_pressed(Action::ToggleStatusPlates) {
+        settings.visible = !settings.visible;
+    }
+}
+
+fn spawn_status_plates(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    units: Query<(Entity, &UnitType), Added<UnitType>>,
+) {
+    for (owner, unit_type) in units.iter() {
+        let style = |color: Color| TextStyle {
+            font: font.0.clone(),
+            font_size: STATUS_PLATE_FONT_SIZE,
+            color,
+        };
+
+        commands.spawn((
+            TextBundle::from_sections([
+                TextSection::new(format!("{} ", unit_type.name()), style(Color::WHITE)),
+                TextSection::new(UnitActivity::Idle.icon(), style(UnitActivity::Idle.color())),
+            ])
+            .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.5)),
+            StatusPlate { owner },
+            WorldAnchor {
+                target: owner,
+                offset: STATUS_PLATE_OFFSET,
+            },
+        ));
+    }
+}
+
+fn update_status_plates(
+    settings: Res<StatusPlateSettings>,
+    camera: Query<&Projection, With<MainCamera>>,
+    units: Query<(Option<&Moving>, Option<&AttackTask>, Option<&BuildTask>)>,
+    mut plates: Query<(&StatusPlate, &mut Text, &mut Visibility)>,
+) {
+    let zoomed_out = match camera.single() {
+        Projection::Orthographic(orthographic) => orthographic.scale > settings.max_zoom,
+        Projection::Perspective(_) => false,
+    };
+    let show = settings.visible && !zoomed_out;
+
+    for (plate, mut text, mut visibility) in plates.iter_mut() {
+        let wanted = if show {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+        if !show {
+            continue;
+        }
+
+        let Ok((moving, attack_task, build_task)) = units.get(plate.owner) else {
+            continue;
+        };
+        // A task takes priority over the walking it involves
+        let activity = if attack_task.is_some() {
+            UnitActivity::Attacking
+        } else if build_task.is_some() {
+            UnitActivity::Building
+        } else if moving.is_some() {
+            UnitActivity::Walking
+        } else {
+            UnitActivity::Idle
+        };
+
+        if text.sections[1].value != activity.icon() {
+            let icon = &mut text.sections[1];
+            icon.value = activity.icon().to_string();
+            icon.style.color = activity.color();
+        }
+    }
+}
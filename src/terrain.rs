@@ -0,0 +1,395 @@
+//! The terrain: a `TileMap` resource says what kind of ground every tile is
+//! and how high each tile corner lies, and the ground mesh is built from it
+//! with a vertex color per tile. Edit the tilemap and the mesh is rebuilt to
+//! match.
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy_mod_picking::prelude::*;
+
+use crate::grid::{tile_at, OccupancyGrid, TILE_SIZE};
+use crate::map_file::load_or_generate_map;
+use crate::rng::GameRng;
+use crate::worldgen::WorldGenerator;
+use crate::{DeselectAllEvent, Ground};
+
+pub struct TerrainPlugin;
+
+/// Steepest rise, in world units per tile, that units can still walk up.
+const MAX_WALKABLE_SLOPE: f32 = 0.8;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldGenerator>()
+            .init_resource::<GameRng>();
+        let (map, tile_map) = app.world.resource_scope(|world, mut rng: Mut<GameRng>| {
+            load_or_generate_map(world.resource::<WorldGenerator>(), &mut *rng)
+        });
+        app.insert_resource(map)
+            .insert_resource(tile_map)
+            .add_startup_system(spawn_ground)
+            .add_system(rebuild_ground_mesh)
+            .add_system(block_impassable_tiles.run_if(resource_changed::<TileMap>()));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileKind {
+    Grass,
+    Forest,
+    Hills,
+    Water,
+    Road,
+}
+
+impl TileKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TileKind::Grass => "Grass",
+            TileKind::Forest => "Forest",
+            TileKind::Hills => "Hills",
+            TileKind::Water => "Water",
+            TileKind::Road => "Road",
+        }
+    }
+
+    /// Character standing for this kind in map files.
+    pub fn symbol(&self) -> char {
+        match self {
+            TileKind::Grass => '.',
+            TileKind::Forest => 'T',
+            TileKind::Hills => '^',
+            TileKind::Water => '~',
+            TileKind::Road => '#',
+        }
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '.' => Some(TileKind::Grass),
+            'T' => Some(TileKind::Forest),
+            '^' => Some(TileKind::Hills),
+            '~' => Some(TileKind::Water),
+            '#' => Some(TileKind::Road),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            TileKind::Grass => Color::rgb(0.3, 0.5, 0.3),
+            TileKind::Forest => Color::rgb(0.16, 0.33, 0.16),
+            TileKind::Hills => Color::rgb(0.45, 0.43, 0.38),
+            TileKind::Water => Color::rgb(0.2, 0.35, 0.6),
+            TileKind::Road => Color::rgb(0.55, 0.47, 0.35),
+        }
+    }
+}
+
+/// The kind of every tile on the map. The map is a rectangle of tiles; any
+/// tile outside it is off the map.
+#[derive(Resource)]
+pub struct TileMap {
+    /// Tile with the lowest x and z coordinates.
+    origin: IVec2,
+    size: UVec2,
+    /// Row by row, starting at `origin`.
+    tiles: Vec<TileKind>,
+    /// Height of every tile corner, row by row. There is one more corner
+    /// than tiles along each side.
+    heights: Vec<f32>,
+}
+
+impl TileMap {
+    /// A map of `size` tiles starting at `origin`, all of one kind.
+    pub fn new(origin: IVec2, size: UVec2, kind: TileKind) -> Self {
+        Self {
+            origin,
+            size,
+            tiles: vec![kind; (size.x * size.y) as usize],
+            heights: vec![0.0; ((size.x + 1) * (size.y + 1)) as usize],
+        }
+    }
+
+    /// Tile with the lowest x and z coordinates.
+    pub fn origin(&self) -> IVec2 {
+        self.origin
+    }
+
+    /// Size of the map in tiles.
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    fn index(&self, tile: IVec2) -> Option<usize> {
+        let offset = tile - self.origin;
+        let size = self.size.as_ivec2();
+        if offset.x < 0 || offset.y < 0 || offset.x >= size.x || offset.y >= size.y {
+            return None;
+        }
+        Some((offset.y * size.x + offset.x) as usize)
+    }
+
+    /// Kind of a tile, or `None` if it is off the map.
+    pub fn get(&self, tile: IVec2) -> Option<TileKind> {
+        self.index(tile).map(|index| self.tiles[index])
+    }
+
+    /// Changes a tile's kind. Tiles off the map are left alone.
+    pub fn set(&mut self, tile: IVec2, kind: TileKind) {
+        if let Some(index) = self.index(tile) {
+            self.tiles[index] = kind;
+        }
+    }
+
+    /// Whether units can walk onto a tile: it is on the map, dry and not
+    /// too steep.
+    pub fn is_walkable(&self, tile: IVec2) -> bool {
+        matches!(self.get(tile), Some(kind) if kind != TileKind::Water)
+            && self.slope(tile) <= MAX_WALKABLE_SLOPE
+    }
+
+    /// `position` if units can walk there, or else the centre of the
+    /// closest tile they can walk on, such as the shore next to water.
+    pub fn nearest_walkable(&self, position: Vec3) -> Option<Vec3> {
+        let tile = tile_at(position);
+        if self.is_walkable(tile) {
+            return Some(position);
+        }
+
+        // Search rings of tiles around the position, widening until one has
+        // a walkable tile
+        let ground = Vec2::new(position.x, position.z);
+        let max_radius =
+            self.size.x.max(self.size.y) as i32 + (tile - self.origin).abs().max_element();
+        for radius in 1..=max_radius {
+            let closest = (-radius..=radius)
+                .flat_map(|x| (-radius..=radius).map(move |y| IVec2::new(x, y)))
+                .filter(|offset| offset.x.abs() == radius || offset.y.abs() == radius)
+                .map(|offset| tile + offset)
+                .filter(|tile| self.is_walkable(*tile))
+                .map(|tile| (tile.as_vec2() + Vec2::splat(0.5)) * TILE_SIZE)
+                .min_by(|a, b| a.distance(ground).total_cmp(&b.distance(ground)));
+            if let Some(center) = closest {
+                return Some(self.ground_position(Vec3::new(center.x, 0.0, center.y)));
+            }
+        }
+        None
+    }
+
+    /// Height of a tile corner, which is also the lowest corner of the tile
+    /// with the same coordinates. Corners off the map take the height of the
+    /// nearest corner on it.
+    pub fn corner_height(&self, corner: IVec2) -> f32 {
+        let offset = (corner - self.origin).clamp(IVec2::ZERO, self.size.as_ivec2());
+        self.heights[(offset.y * (self.size.x as i32 + 1) + offset.x) as usize]
+    }
+
+    /// Changes the height of a tile corner. Corners off the map are left
+    /// alone.
+    pub fn set_corner_height(&mut self, corner: IVec2, height: f32) {
+        let offset = corner - self.origin;
+        let size = self.size.as_ivec2();
+        if offset.x < 0 || offset.y < 0 || offset.x > size.x || offset.y > size.y {
+            return;
+        }
+        self.heights[(offset.y * (size.x + 1) + offset.x) as usize] = height;
+    }
+
+    /// Ground height under a world position, interpolated between the
+    /// corners of its tile.
+    pub fn height_at(&self, position: Vec3) -> f32 {
+        let position = Vec2::new(position.x, position.z) / TILE_SIZE;
+        let corner = position.floor().as_ivec2();
+        let t = position - position.floor();
+        let height = |x: i32, y: i32| self.corner_height(corner + IVec2::new(x, y));
+        let top = height(0, 0) + (height(1, 0) - height(0, 0)) * t.x;
+        let bottom = height(0, 1) + (height(1, 1) - height(0, 1)) * t.x;
+        top + (bottom - top) * t.y
+    }
+
+    /// `position` moved onto the ground.
+    pub fn ground_position(&self, position: Vec3) -> Vec3 {
+        Vec3::new(position.x, self.height_at(position), position.z)
+    }
+
+    /// Height of the highest corner on the map.
+    pub fn max_height(&self) -> f32 {
+        self.heights.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Difference between a tile's highest and lowest corner, per tile.
+    fn slope(&self, tile: IVec2) -> f32 {
+        let heights = [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE]
+            .map(|corner| self.corner_height(tile + corner));
+        let highest = heights.iter().copied().fold(f32::MIN, f32::max);
+        let lowest = heights.iter().copied().fold(f32::MAX, f32::min);
+        (highest - lowest) / TILE_SIZE
+    }
+
+    /// Normal of the terrain at a tile corner, averaged over its
+    /// neighbours so the hills shade smoothly.
+    fn corner_normal(&self, corner: IVec2) -> Vec3 {
+        let height = |x: i32, y: i32| self.corner_height(corner + IVec2::new(x, y));
+        Vec3::new(
+            height(-1, 0) - height(1, 0),
+            2.0 * TILE_SIZE,
+            height(0, -1) - height(0, 1),
+        )
+        .normalize()
+    }
+
+    /// Extent of the map on the XZ plane, in world units.
+    pub fn bounds(&self) -> Rect {
+        let min = self.origin.as_vec2() * TILE_SIZE;
+        Rect::from_corners(min, min + self.size.as_vec2() * TILE_SIZE)
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = (IVec2, TileKind)> + '_ {
+        self.tiles.iter().enumerate().map(|(index, kind)| {
+            let index = index as i32;
+            let width = self.size.x as i32;
+            (
+                self.origin + IVec2::new(index % width, index / width),
+                *kind,
+            )
+        })
+    }
+
+    /// A mesh following the corner heights with a quad per tile, colored by
+    /// kind.
+    pub fn mesh(&self) -> Mesh {
+        let tile_count = self.tiles.len();
+        let mut positions = Vec::with_capacity(tile_count * 4);
+        let mut normals = Vec::with_capacity(tile_count * 4);
+        let mut colors = Vec::with_capacity(tile_count * 4);
+        let mut indices = Vec::with_capacity(tile_count * 6);
+
+        for (tile, kind) in self.tiles() {
+            let first = positions.len() as u32;
+            for corner in [IVec2::ZERO, IVec2::X, IVec2::ONE, IVec2::Y] {
+                let corner = tile + corner;
+                let position = corner.as_vec2() * TILE_SIZE;
+                positions.push([position.x, self.corner_height(corner), position.y]);
+                normals.push(self.corner_normal(corner).to_array());
+            }
+            colors.extend([kind.color().as_linear_rgba_f32(); 4]);
+            // Counter-clockwise seen from above, so the quad faces up
+            indices.extend([first, first + 3, first + 2, first, first + 2, first + 1]);
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+}
+
+fn spawn_ground(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    tile_map: Res<TileMap>,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(tile_map.mesh()),
+            // White so the vertex colors show as they are
+            material: materials.add(Color::WHITE.into()),
+            ..default()
+        },
+        Ground,
+        OnPointer::<Click>::send_event::<DeselectAllEvent>(),
+        PickHighlight,
+    ));
+}
+
+/// Keeps units off water and steep slopes, whenever the map is loaded or
+/// edited.
+fn block_impassable_tiles(tile_map: Res<TileMap>, mut grid: ResMut<OccupancyGrid>) {
+    grid.set_impassable(
+        tile_map
+            .tiles()
+            .map(|(tile, _)| tile)
+            .filter(|tile| !tile_map.is_walkable(*tile)),
+    );
+}
+
+fn rebuild_ground_mesh(
+    tile_map: Res<TileMap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    ground: Query<&Handle<Mesh>, With<Ground>>,
+) {
+    if !tile_map.is_changed() || tile_map.is_added() {
+        return;
+    }
+
+    for handle in ground.iter() {
+        if let Some(mesh) = meshes.get_mut(handle) {
+            *mesh = tile_map.mesh();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flat 5 by 5 grass map with a single water tile in the middle.
+    fn pond() -> TileMap {
+        let mut tile_map = TileMap::new(IVec2::ZERO, UVec2::splat(5), TileKind::Grass);
+        tile_map.set(IVec2::new(2, 2), TileKind::Water);
+        tile_map
+    }
+
+    #[test]
+    fn nearest_walkable_keeps_walkable_positions() {
+        let position = Vec3::new(0.3, 0.0, 4.7);
+        assert_eq!(pond().nearest_walkable(position), Some(position));
+    }
+
+    #[test]
+    fn nearest_walkable_from_water() {
+        let tile_map = pond();
+        let shore = tile_map.nearest_walkable(Vec3::new(2.5, 0.0, 2.3)).unwrap();
+        // The closest neighbouring tile, not a diagonal one
+        assert_eq!(shore, Vec3::new(2.5, 0.0, 1.5));
+        assert!(tile_map.is_walkable(tile_at(shore)));
+    }
+
+    #[test]
+    fn nearest_walkable_from_off_the_map() {
+        let tile_map = pond();
+        let edge = tile_map
+            .nearest_walkable(Vec3::new(-3.0, 0.0, 1.2))
+            .unwrap();
+        assert_eq!(edge, Vec3::new(0.5, 0.0, 1.5));
+
+        let corner = tile_map
+            .nearest_walkable(Vec3::new(9.0, 0.0, -4.0))
+            .unwrap();
+        assert_eq!(corner, Vec3::new(4.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn nearest_walkable_avoids_steep_slopes() {
+        let mut tile_map = TileMap::new(IVec2::ZERO, UVec2::new(3, 1), TileKind::Grass);
+        // A cliff rising along the middle tile
+        tile_map.set_corner_height(IVec2::new(2, 0), 2.0);
+        tile_map.set_corner_height(IVec2::new(2, 1), 2.0);
+        tile_map.set_corner_height(IVec2::new(3, 0), 2.0);
+        tile_map.set_corner_height(IVec2::new(3, 1), 2.0);
+        assert!(!tile_map.is_walkable(IVec2::new(1, 0)));
+
+        let foot = tile_map.nearest_walkable(Vec3::new(1.4, 0.0, 0.5)).unwrap();
+        assert_eq!(foot, Vec3::new(0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn nothing_walkable_on_a_lake() {
+        let lake = TileMap::new(IVec2::ZERO, UVec2::splat(3), TileKind::Water);
+        assert_eq!(lake.nearest_walkable(Vec3::new(1.5, 0.0, 1.5)), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! The fixed tick. Movement, combat, construction and NPCs step in
+//! `CoreSchedule::FixedUpdate`, `TICKS_PER_SECOND` times a second, so the
+//! game plays out the same whatever the frame rate. `Transform` holds where
+//! things are as of the last tick; `Interpolated` entities are drawn part
+//! way between their last two ticks so they still move smoothly.
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+use crate::Gameplay;
+
+pub struct TickPlugin;
+
+impl Plugin for TickPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FixedTime::new_from_secs(1.0 / TICKS_PER_SECOND))
+            .add_system(
+                start_tick
+                    .before(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                end_tick
+                    .after(Gameplay)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                interpolate_transforms
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+const TICKS_PER_SECOND: f32 = 20.0;
+
+/// Drawn between its last two ticks instead of jumping from one to the
+/// next.
+#[derive(Component, Default)]
+pub struct Interpolated {
+    /// Transform before the last tick, once a tick has run.
+    previous: Option<Transform>,
+    /// Transform after the last tick.
+    current: Transform,
+}
+
+fn start_tick(mut entities: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in entities.iter_mut() {
+        interpolated.previous = Some(*transform);
+    }
+}
+
+fn end_tick(mut entities: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in entities.iter_mut() {
+        interpolated.current = *transform;
+    }
+}
+
+/// Moves interpolated entities, and everything attached to them, to where
+/// they are drawn this frame. Only works on entities without a parent.
+fn interpolate_transforms(
+    fixed_time: Res<FixedTime>,
+    mut entities: Query<(
+        &Transform,
+        &Interpolated,
+        &mut GlobalTransform,
+        Option<&Children>,
+    )>,
+    mut descendants: Query<&mut GlobalTransform, Without<Interpolated>>,
+    children: Query<&Children>,
+) {
+    // How far the game is towards the next tick
+    let blend = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.0);
+
+    for (transform, interpolated, mut global_transform, entity_children) in entities.iter_mut() {
+        let Some(previous) = interpolated.previous else {
+            continue;
+        };
+        // Moved outside of a tick, such as by loading a save
+        if *transform != interpolated.current {
+            continue;
+        }
+
+        let drawn = GlobalTransform::from(Transform {
+            translation: previous.translation.lerp(transform.translation, blend),
+            rotation: previous.rotation.slerp(transform.rotation, blend),
+            scale: transform.scale,
+        });
+        // Whatever the entity's global transform is now, its children's
+        // match it, so the same change puts them where they're drawn too
+        let correction =
+            GlobalTransform::from(drawn.affine() * global_transform.affine().inverse());
+        *global_transform = drawn;
+
+        let mut stack: Vec<Entity> = entity_children
+            .map(|entity_children| entity_children.to_vec())
+            .unwrap_or_default();
+        while let Some(child) = stack.pop() {
+            if let Ok(mut child_transform) = descendants.get_mut(child) {
+                *child_transform = correction * *child_transform;
+            }
+            if let Ok(grandchildren) = children.get(child) {
+                stack.extend(grandchildren.iter());
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Hover tooltips for UI nodes. Any node with an `Interaction` can carry a
+//! `Tooltip`; after the cursor rests on it for a moment a single shared popup
+//! shows the text next to the cursor.
+
+use bevy::prelude::*;
+
+use crate::ui::UiFont;
+
+pub struct TooltipPlugin;
+
+impl Plugin for TooltipPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TooltipSettings>()
+            .add_startup_system(spawn_tooltip_popup)
+            .add_system(update_tooltip_popup);
+    }
+}
+
+/// Distance from the cursor to the popup's top-left corner, in logical
+/// pixels.
+const POPUP_OFFSET: Vec2 = Vec2::new(14.0, 18.0);
+
+#[derive(Resource)]
+pub struct TooltipSettings {
+    /// Seconds the cursor has to rest on a node before its tooltip shows.
+    pub delay: f32,
+}
+
+impl Default for TooltipSettings {
+    fn default() -> Self {
+        Self { delay: 0.5 }
+    }
+}
+
+/// Text shown when hovering this UI node.
+#[derive(Component)]
+pub struct Tooltip(pub String);
+
+#[derive(Component)]
+struct TooltipPopup;
+
+fn spawn_tooltip_popup(mut commands: Commands, font: Res<UiFont>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font.0.clone(),
+                font_size: 13.0,
+                color: Color::rgb(1.0, 0.95, 0.8),
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(4.0)),
+            max_size: Size::width(Val::Px(260.0)),
+            display: Display::None,
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.05, 0.05, 0.05, 0.9)),
+        ZIndex::Global(100),
+        TooltipPopup,
+    ));
+}
+
+fn update_tooltip_popup(
+    time: Res<Time>,
+    settings: Res<TooltipSettings>,
+    windows: Query<&Window>,
+    hovered: Query<(Entity, &Interaction, &Tooltip)>,
+    mut popup: Query<(&mut Style, &mut Text), With<TooltipPopup>>,
+    mut hover_start: Local<Option<(Entity, f32)>>,
+) {
+    // Unaffected by pausing, so tooltips work in the pause menu
+    let now = time.raw_elapsed_seconds();
+    let target = hovered
+        .iter()
+        .find(|(_, interaction, _)| **interaction != Interaction::None);
+
+    // Restart the delay whenever the cursor moves onto a different node
+    let hovered_entity = target.map(|(entity, ..)| entity);
+    if hover_start.map(|(entity, _)| entity) != hovered_entity {
+        *hover_start = hovered_entity.map(|entity| (entity, now));
+    }
+
+    let window = windows.single();
+    let cursor = window.cursor_position();
+    let show = match (*hover_start, target, cursor) {
+        (Some((_, since)), Some((_, _, tooltip)), Some(cursor))
+            if now - since >= settings.delay =>
+        {
+            Some((tooltip, cursor))
+        }
+        _ => None,
+    };
+
+    for (mut style, mut text) in popup.iter_mut() {
+        let Some((tooltip, cursor)) = show else {
+            if style.display != Display::None {
+                style.display = Display::None;
+            }
+            continue;
+        };
+
+        // Cursor positions start at the bottom-left corner, UI at the top-left
+        style.display = Display::Flex;
+        style.position.left = Val::Px(cursor.x + POPUP_OFFSET.x);
+        style.position.top = Val::Px(window.height() - cursor.y + POPUP_OFFSET.y);
+        if text.sections[0].value != tooltip.0 {
+            text.sections[0].value = tooltip.0.clone();
+        }
+    }
+}
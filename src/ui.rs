@@ -0,0 +1,125 @@
+//! Shared UI state used by world-input systems, and UI nodes that follow
+//! entities in the world.
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy::ui::UiSystem;
+
+use crate::camera::MainCamera;
+
+pub struct GameUiPlugin;
+
+impl Plugin for GameUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UiFont>()
+            .init_resource::<PointerOverUi>()
+            .add_system(
+                update_pointer_over_ui
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(UiSystem::Focus),
+            )
+            .add_system(
+                position_world_anchored_nodes
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(TransformSystem::TransformPropagate)
+                    .before(UiSystem::Flex),
+            );
+    }
+}
+
+/// Font used by all in-game text.
+#[derive(Resource)]
+pub struct UiFont(pub Handle<Font>);
+
+impl FromWorld for UiFont {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        UiFont(asset_server.load("fonts/DejaVuSans-Bold.ttf"))
+    }
+}
+
+/// Whether the cursor is currently over a UI panel.
+///
+/// World-input systems (move orders, selection, edge scrolling, zoom) check
+/// this so clicks never fall through a panel into the scene. Panels opt in by
+/// carrying an `Interaction` component, which Bevy keeps up to date.
+#[derive(Resource, Default)]
+pub struct PointerOverUi(pub bool);
+
+fn update_pointer_over_ui(
+    interactions: Query<&Interaction, With<Node>>,
+    mut pointer_over_ui: ResMut<PointerOverUi>,
+) {
+    let over_ui = interactions
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+
+    if pointer_over_ui.0 != over_ui {
+        pointer_over_ui.0 = over_ui;
+    }
+}
+
+/// A transient panel, like the build menu, that Escape closes. Escape only
+/// opens the pause menu while no popup is open.
+#[derive(Component)]
+pub struct Popup;
+
+/// Keeps an absolutely positioned UI node centred over a point above `target`,
+/// so it reads like a billboard in the world. The node is despawned together
+/// with its target, unless it also has [`OutlivesTarget`].
+#[derive(Component)]
+pub struct WorldAnchor {
+    pub target: Entity,
+    /// World-space offset from the target's origin.
+    pub offset: Vec3,
+}
+
+/// Keeps a world-anchored node where its target was last seen once the
+/// target is gone, rather than despawning it with the target. Whatever
+/// spawned the node despawns it.
+#[derive(Component)]
+pub struct OutlivesTarget {
+    pub last_position: Vec3,
+}
+
+fn position_world_anchored_nodes(
+    mut commands: Commands,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    targets: Query<&GlobalTransform>,
+    mut nodes: Query<(
+        Entity,
+        &WorldAnchor,
+        &Node,
+        &mut Style,
+        Option<&mut OutlivesTarget>,
+    )>,
+) {
+    let (camera, camera_transform) = camera.single();
+
+    for (entity, anchor, node, mut style, outlives) in nodes.iter_mut() {
+        let target_position = match (targets.get(anchor.target), outlives) {
+            (Ok(target), Some(mut outlives)) => {
+                outlives.last_position = target.translation();
+                outlives.last_position
+            }
+            (Ok(target), None) => target.translation(),
+            (Err(_), Some(outlives)) => outlives.last_position,
+            (Err(_), None) => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+
+        let world_position = target_position + anchor.offset;
+        let Some(screen_position) = camera.world_to_viewport(camera_transform, world_position)
+        else {
+            continue;
+        };
+
+        // Viewport coordinates start at the bottom-left corner.
+        let size = node.size();
+        style.position_type = PositionType::Absolute;
+        style.position.left = Val::Px(screen_position.x - size.x / 2.0);
+        style.position.bottom = Val::Px(screen_position.y);
+    }
+}
@@ -0,0 +1,204 @@
+//! Water: a rippling translucent surface over every water tile, and fishing
+//! spots bubbling along the banks. Water tiles can't be walked on, so move
+//! orders onto water go to the nearest shore instead.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use rand::Rng;
+
+use crate::grid::TILE_SIZE;
+use crate::rng::GameRng;
+use crate::terrain::{TileKind, TileMap};
+
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_water_surface)
+            // After the goblins, so the same seed always places the same
+            // spots
+            .add_startup_system(spawn_fishing_spots.in_base_set(StartupSet::PostStartup))
+            .add_system(rebuild_water_surface)
+            .add_system(animate_water)
+            .add_system(bob_fishing_spots);
+    }
+}
+
+/// Height of the water surface above the lowest corner of its tile.
+const WATER_DEPTH: f32 = 0.08;
+const WATER_COLOR: Color = Color::rgba(0.2, 0.4, 0.75, 0.75);
+/// Chance of a fishing spot on each bank tile.
+const FISHING_SPOT_CHANCE: f64 = 0.15;
+
+/// The surface of all the water on the map.
+#[derive(Component)]
+pub struct WaterSurface;
+
+/// A spot in the water by the bank where fish gather.
+#[derive(Component)]
+pub struct FishingSpot {
+    pub tile: IVec2,
+    /// Offset of this spot's bobbing, so spots don't bob in step.
+    phase: f32,
+}
+
+/// Whether a tile is water next to a tile units can stand on.
+pub fn is_bank(tile_map: &TileMap, tile: IVec2) -> bool {
+    tile_map.get(tile) == Some(TileKind::Water)
+        && [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+            .into_iter()
+            .any(|offset| tile_map.is_walkable(tile + offset))
+}
+
+/// Height of the water surface on a tile.
+fn water_level(tile_map: &TileMap, tile: IVec2) -> f32 {
+    let lowest = [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE]
+        .map(|corner| tile_map.corner_height(tile + corner))
+        .into_iter()
+        .fold(f32::MAX, f32::min);
+    lowest + WATER_DEPTH
+}
+
+fn water_mesh(tile_map: &TileMap) -> Mesh {
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+
+    for (tile, kind) in tile_map.tiles() {
+        if kind != TileKind::Water {
+            continue;
+        }
+        let level = water_level(tile_map, tile);
+        let first = positions.len() as u32;
+        for corner in [IVec2::ZERO, IVec2::X, IVec2::ONE, IVec2::Y] {
+            let position = (tile + corner).as_vec2() * TILE_SIZE;
+            positions.push([position.x, level, position.y]);
+        }
+        // Counter-clockwise seen from above, so the quad faces up
+        indices.extend([first, first + 3, first + 2, first, first + 2, first + 1]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 1.0, 0.0]; positions.len()],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn spawn_water_surface(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    tile_map: Res<TileMap>,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(water_mesh(&tile_map)),
+            material: materials.add(StandardMaterial {
+                base_color: WATER_COLOR,
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 0.1,
+                reflectance: 0.6,
+                ..default()
+            }),
+            ..default()
+        },
+        WaterSurface,
+    ));
+}
+
+/// Reshapes the surface when the tilemap changes, and dries up fishing
+/// spots that are no longer by a bank.
+fn rebuild_water_surface(
+    mut commands: Commands,
+    tile_map: Res<TileMap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    surface: Query<&Handle<Mesh>, With<WaterSurface>>,
+    spots: Query<(Entity, &FishingSpot)>,
+) {
+    if !tile_map.is_changed() || tile_map.is_added() {
+        return;
+    }
+
+    for handle in surface.iter() {
+        if let Some(mesh) = meshes.get_mut(handle) {
+            *mesh = water_mesh(&tile_map);
+        }
+    }
+    for (entity, spot) in spots.iter() {
+        if !is_bank(&tile_map, spot.tile) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Makes the water shimmer by slowly shifting its color and opacity.
+fn animate_water(
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    surface: Query<&Handle<StandardMaterial>, With<WaterSurface>>,
+) {
+    let wave = (time.elapsed_seconds() * 1.5).sin();
+    for handle in surface.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            let mut color = WATER_COLOR;
+            color.set_g(WATER_COLOR.g() + 0.04 * wave);
+            color.set_a(WATER_COLOR.a() + 0.08 * wave);
+            material.base_color = color;
+        }
+    }
+}
+
+pub fn spawn_fishing_spots(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    tile_map: Res<TileMap>,
+    mut rng: ResMut<GameRng>,
+) {
+    let mesh = meshes.add(
+        shape::UVSphere {
+            radius: 0.12,
+            ..default()
+        }
+        .into(),
+    );
+    let material = materials.add(Color::rgba(0.9, 0.95, 1.0, 0.8).into());
+
+    for (tile, _) in tile_map.tiles() {
+        if !is_bank(&tile_map, tile) || !rng.gen_bool(FISHING_SPOT_CHANCE) {
+            continue;
+        }
+
+        let center = (tile.as_vec2() + Vec2::splat(0.5)) * TILE_SIZE;
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_xyz(center.x, water_level(&tile_map, tile), center.y)
+                    .with_scale(Vec3::new(1.0, 0.3, 1.0)),
+                ..default()
+            },
+            Name::new("Fishing spot"),
+            FishingSpot {
+                tile,
+                phase: rng.gen_range(0.0..TAU),
+            },
+        ));
+    }
+}
+
+fn bob_fishing_spots(
+    time: Res<Time>,
+    tile_map: Res<TileMap>,
+    mut spots: Query<(&FishingSpot, &mut Transform)>,
+) {
+    for (spot, mut transform) in spots.iter_mut() {
+        let bob = (time.elapsed_seconds() * 3.0 + spot.phase).sin() * 0.02;
+        transform.translation.y = water_level(&tile_map, spot.tile) + bob;
+    }
+}
@@ -0,0 +1,231 @@
+//! The world generator: fills a new tilemap with biomes from two layers of
+//! smooth noise, one for elevation and one for moisture. Low ground floods
+//! into water, high ground turns into rocky hills, and the rest is forest or
+//! plains depending on how wet it is. The same elevation raises the ground
+//! above the water into hills and valleys. Roads are laid over the top
+//! afterwards so the middle of the map is always reachable.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::grid::TILE_SIZE;
+use crate::terrain::{TileKind, TileMap};
+use crate::MAP_SIZE;
+
+#[derive(Resource)]
+pub struct WorldGenerator {
+    /// Map size in tiles, centred on the origin.
+    pub size: UVec2,
+    /// Tiles across the largest features of the noise.
+    pub feature_size: f32,
+    /// Elevation below which tiles are water.
+    pub water_level: f32,
+    /// Elevation above which tiles are rocky hills.
+    pub hill_level: f32,
+    /// World units the ground rises per unit of elevation above the water.
+    pub height_scale: f32,
+    /// Moisture above which tiles are forest.
+    pub forest_moisture: f32,
+    /// Distance from the centre of the map within which tiles are always
+    /// plains, so the starting units have room to move.
+    pub clearing_radius: f32,
+}
+
+impl Default for WorldGenerator {
+    fn default() -> Self {
+        let tiles = (MAP_SIZE / TILE_SIZE) as u32;
+        Self {
+            size: UVec2::splat(tiles),
+            feature_size: 8.0,
+            water_level: 0.3,
+            hill_level: 0.68,
+            height_scale: 4.0,
+            forest_moisture: 0.55,
+            clearing_radius: 3.0,
+        }
+    }
+}
+
+impl WorldGenerator {
+    pub fn generate(&self, rng: &mut impl Rng) -> TileMap {
+        let origin = -(self.size / 2).as_ivec2();
+        let elevation = Noise::new(rng, self.size, self.feature_size);
+        let moisture = Noise::new(rng, self.size, self.feature_size);
+
+        let mut map = TileMap::new(origin, self.size, TileKind::Grass);
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let offset = UVec2::new(x, y).as_vec2();
+                let tile = origin + UVec2::new(x, y).as_ivec2();
+                let center = (tile.as_vec2() + Vec2::splat(0.5)) * TILE_SIZE;
+
+                let kind = if center.length() < self.clearing_radius {
+                    TileKind::Grass
+                } else {
+                    let elevation = elevation.sample(offset);
+                    if elevation < self.water_level {
+                        TileKind::Water
+                    } else if elevation > self.hill_level {
+                        TileKind::Hills
+                    } else if moisture.sample(offset) > self.forest_moisture {
+                        TileKind::Forest
+                    } else {
+                        TileKind::Grass
+                    }
+                };
+                map.set(tile, kind);
+            }
+        }
+
+        // Water and the clearing stay level with the shore
+        for y in 0..=self.size.y {
+            for x in 0..=self.size.x {
+                let corner = origin + UVec2::new(x, y).as_ivec2();
+                if (corner.as_vec2() * TILE_SIZE).length() < self.clearing_radius {
+                    continue;
+                }
+                let elevation = elevation.sample(UVec2::new(x, y).as_vec2());
+                let height = (elevation - self.water_level).max(0.0) * self.height_scale;
+                map.set_corner_height(corner, height);
+            }
+        }
+
+        // Two roads crossing in the middle, bridging any water they meet
+        let size = self.size.as_ivec2();
+        for i in origin.x..origin.x + size.x {
+            map.set(IVec2::new(i, -1), TileKind::Road);
+            map.set(IVec2::new(i, 0), TileKind::Road);
+        }
+        for i in origin.y..origin.y + size.y {
+            map.set(IVec2::new(-1, i), TileKind::Road);
+            map.set(IVec2::new(0, i), TileKind::Road);
+        }
+
+        map
+    }
+}
+
+/// Value noise: random values on a coarse lattice, smoothly interpolated in
+/// between, with a finer layer on top for detail. Samples are between 0
+/// and 1.
+struct Noise {
+    coarse: Lattice,
+    fine: Lattice,
+}
+
+impl Noise {
+    fn new(rng: &mut impl Rng, size: UVec2, feature_size: f32) -> Self {
+        Self {
+            coarse: Lattice::new(rng, size, feature_size),
+            fine: Lattice::new(rng, size, feature_size / 3.0),
+        }
+    }
+
+    /// Noise at `position`, in tiles from the map's corner.
+    fn sample(&self, position: Vec2) -> f32 {
+        0.7 * self.coarse.sample(position) + 0.3 * self.fine.sample(position)
+    }
+}
+
+struct Lattice {
+    values: Vec<f32>,
+    width: usize,
+    spacing: f32,
+}
+
+impl Lattice {
+    fn new(rng: &mut impl Rng, size: UVec2, spacing: f32) -> Self {
+        // One extra point past each edge so every tile has four corners
+        let width = (size.x as f32 / spacing).ceil() as usize + 2;
+        let height = (size.y as f32 / spacing).ceil() as usize + 2;
+        Self {
+            values: (0..width * height).map(|_| rng.gen()).collect(),
+            width,
+            spacing,
+        }
+    }
+
+    fn sample(&self, position: Vec2) -> f32 {
+        let position = position / self.spacing;
+        let cell = position.floor();
+        let t = position - cell;
+        // Smoothstep hides the lattice's grid lines
+        let t = t * t * (Vec2::splat(3.0) - 2.0 * t);
+
+        let (x, y) = (cell.x as usize, cell.y as usize);
+        let value = |x: usize, y: usize| self.values[y * self.width + x];
+        let top = value(x, y) + (value(x + 1, y) - value(x, y)) * t.x;
+        let bottom = value(x, y + 1) + (value(x + 1, y + 1) - value(x, y + 1)) * t.x;
+        top + (bottom - top) * t.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::doodads::{scatter_doodads, Doodad, DoodadKind};
+    use crate::grid::OccupancyGrid;
+    use crate::map_file::MapData;
+    use crate::npc::{spawn_goblins, GoblinAssets, NpcSpawnConfig};
+    use crate::rng::GameRng;
+    use crate::UnitType;
+
+    struct GeneratedWorld {
+        map: MapData,
+        doodads: Vec<(DoodadKind, IVec2, Vec3)>,
+        goblins: Vec<Vec3>,
+    }
+
+    /// Generates the world for `seed`, then places the doodads and goblins
+    /// on it in the order the game does.
+    fn generate_world(seed: u64) -> GeneratedWorld {
+        let mut rng = GameRng::new(seed);
+        let tile_map = WorldGenerator::default().generate(&mut rng);
+        let map = MapData::from_tile_map(&tile_map, Vec::new(), vec![(0.0, 0.0)]);
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .insert_resource(map)
+            .insert_resource(tile_map)
+            .insert_resource(rng)
+            .init_resource::<OccupancyGrid>()
+            .init_resource::<NpcSpawnConfig>()
+            .init_resource::<GoblinAssets>()
+            .add_startup_system(spawn_goblins)
+            .add_startup_system(scatter_doodads.in_base_set(StartupSet::PostStartup));
+        app.update();
+
+        let world = &mut app.world;
+        let doodads = world
+            .query::<(&Doodad, &Transform)>()
+            .iter(world)
+            .map(|(doodad, transform)| (doodad.kind, doodad.tile, transform.translation))
+            .collect();
+        let goblins = world
+            .query::<(&UnitType, &Transform)>()
+            .iter(world)
+            .filter(|(unit_type, _)| **unit_type == UnitType::Goblin)
+            .map(|(_, transform)| transform.translation)
+            .collect();
+        GeneratedWorld {
+            map: app.world.remove_resource().unwrap(),
+            doodads,
+            goblins,
+        }
+    }
+
+    #[test]
+    fn same_seed_same_world() {
+        let (first, second) = (generate_world(42), generate_world(42));
+        assert_eq!(first.map.tiles, second.map.tiles);
+        assert_eq!(first.map.heights, second.map.heights);
+        assert_eq!(first.doodads, second.doodads);
+        assert_eq!(first.goblins, second.goblins);
+        assert!(!first.doodads.is_empty() && !first.goblins.is_empty());
+
+        let other = generate_world(43);
+        assert_ne!(first.map.tiles, other.map.tiles);
+    }
+}